@@ -0,0 +1,80 @@
+//! Verifying bearer tokens in an axum handler.
+//!
+//! Run the `jwks` example first, then:
+//!
+//! ```sh
+//! TOKEN=$(curl -s http://127.0.0.1:3000/token | jq -r .token)
+//! curl -H "Authorization: Bearer $TOKEN" http://127.0.0.1:3001/
+//! ```
+
+#[cfg(feature = "remote-jwks")]
+mod auth {
+    use std::sync::Arc;
+
+    use axum::{
+        async_trait,
+        extract::{FromRequest, RequestParts},
+        http::StatusCode,
+    };
+    use jwtk::{jwk::RemoteJwksVerifier, strip_bearer, HeaderAndClaims};
+    use serde_json::{Map, Value};
+
+    /// Verified claims of the request bearer token.
+    pub struct Auth(pub HeaderAndClaims<Map<String, Value>>);
+
+    #[async_trait]
+    impl<B: Send> FromRequest<B> for Auth {
+        type Rejection = StatusCode;
+
+        async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+            let verifier = req
+                .extensions()
+                .and_then(|e| e.get::<Arc<RemoteJwksVerifier>>())
+                .cloned()
+                .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+            let token = req
+                .headers()
+                .and_then(|h| h.get("authorization"))
+                .and_then(|v| v.to_str().ok())
+                .and_then(strip_bearer)
+                .ok_or(StatusCode::UNAUTHORIZED)?;
+            let claims = verifier
+                .verify(token)
+                .await
+                .map_err(|_| StatusCode::UNAUTHORIZED)?;
+            Ok(Auth(claims))
+        }
+    }
+}
+
+#[cfg(feature = "remote-jwks")]
+#[tokio::main]
+async fn main() {
+    use std::{net::Ipv4Addr, sync::Arc, time::Duration};
+
+    use axum::{prelude::*, AddExtensionLayer};
+    use jwtk::jwk::RemoteJwksVerifier;
+
+    async fn handler(auth::Auth(claims): auth::Auth) -> String {
+        format!(
+            "hello {}",
+            claims.claims().sub.as_deref().unwrap_or("anonymous")
+        )
+    }
+
+    let verifier = Arc::new(RemoteJwksVerifier::new_allow_insecure_http(
+        "http://127.0.0.1:3000/jwks".into(),
+        None,
+        Duration::from_secs(300),
+    ));
+
+    let app = route("/", get(handler)).layer(AddExtensionLayer::new(verifier));
+
+    axum::Server::bind(&(Ipv4Addr::LOCALHOST, 3001).into())
+        .serve(app.into_make_service())
+        .await
+        .unwrap();
+}
+
+#[cfg(not(feature = "remote-jwks"))]
+fn main() {}
@@ -17,7 +17,7 @@ async fn main() -> jwtk::Result<()> {
         .json()
         .await?;
 
-    let j = RemoteJwksVerifier::new(
+    let j = RemoteJwksVerifier::new_allow_insecure_http(
         "http://127.0.0.1:3000/jwks".into(),
         None,
         Duration::from_secs(300),
@@ -1,11 +1,12 @@
-use jwtk::{
-    ecdsa::{EcdsaAlgorithm, EcdsaPrivateKey, EcdsaPublicKey},
-    sign, verify, HeaderAndClaims,
-};
-use serde_json::{Map, Value};
-use std::time::Duration;
-
+#[cfg(all(feature = "sign", feature = "ecdsa"))]
 fn main() -> jwtk::Result<()> {
+    use jwtk::{
+        ecdsa::{EcdsaAlgorithm, EcdsaPrivateKey, EcdsaPublicKey},
+        sign, verify, HeaderAndClaims,
+    };
+    use serde_json::{Map, Value};
+    use std::time::Duration;
+
     let k = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
 
     let pem = k.public_key_to_pem()?;
@@ -26,3 +27,6 @@ fn main() -> jwtk::Result<()> {
 
     Ok(())
 }
+
+#[cfg(not(all(feature = "sign", feature = "ecdsa")))]
+fn main() {}
@@ -1,19 +1,16 @@
-use foreign_types::ForeignTypeRef;
 use openssl::{
     bn::{BigNum, BigNumContext},
     ec::{EcGroup, EcKey},
-    ecdsa::EcdsaSig,
+    ecdsa::{EcdsaSig, EcdsaSigRef},
     hash::{hash, MessageDigest},
     nid::Nid,
     pkey::{HasPublic, PKey, PKeyRef, Private, Public},
 };
-use openssl_sys::BN_bn2bin;
-use smallvec::{smallvec, SmallVec};
+use smallvec::SmallVec;
 
-use crate::{
-    jwk::Jwk, url_safe_trailing_bits, Error, PrivateKeyToJwk, PublicKeyToJwk, Result, SigningKey,
-    VerificationKey,
-};
+use crate::{jwk::Jwk, url_safe_trailing_bits, Error, PublicKeyToJwk, Result, VerificationKey};
+#[cfg(feature = "sign")]
+use crate::{PrivateKeyToJwk, SigningKey};
 
 #[non_exhaustive]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -23,9 +20,34 @@ pub enum EcdsaAlgorithm {
     ES256K,
     ES384,
     ES512,
+    /// ECDSA using brainpoolP256r1 and SHA-256.
+    ///
+    /// Brainpool curves are not in the JWA registry: the `alg` names
+    /// `BP256R1`/`BP384R1`/`BP512R1` and the `crv` names
+    /// `brainpoolP256r1`/`brainpoolP384r1`/`brainpoolP512r1` (the OpenSSL curve
+    /// names) are used for interop.
+    #[cfg(feature = "brainpool")]
+    BP256R1,
+    /// ECDSA using brainpoolP384r1 and SHA-384.
+    #[cfg(feature = "brainpool")]
+    BP384R1,
+    /// ECDSA using brainpoolP512r1 and SHA-512.
+    #[cfg(feature = "brainpool")]
+    BP512R1,
 }
 
 impl EcdsaAlgorithm {
+    /// All ECDSA algorithms, including the brainpool ones if the `brainpool`
+    /// feature is enabled.
+    pub fn all() -> &'static [Self] {
+        use EcdsaAlgorithm::*;
+        #[cfg(not(feature = "brainpool"))]
+        const ALL: &[EcdsaAlgorithm] = &[ES256, ES256K, ES384, ES512];
+        #[cfg(feature = "brainpool")]
+        const ALL: &[EcdsaAlgorithm] = &[ES256, ES256K, ES384, ES512, BP256R1, BP384R1, BP512R1];
+        ALL
+    }
+
     fn curve(self) -> Nid {
         use EcdsaAlgorithm::*;
         match self {
@@ -33,6 +55,12 @@ impl EcdsaAlgorithm {
             ES256K => Nid::SECP256K1,
             ES384 => Nid::SECP384R1,
             ES512 => Nid::SECP521R1,
+            #[cfg(feature = "brainpool")]
+            BP256R1 => Nid::BRAINPOOL_P256R1,
+            #[cfg(feature = "brainpool")]
+            BP384R1 => Nid::BRAINPOOL_P384R1,
+            #[cfg(feature = "brainpool")]
+            BP512R1 => Nid::BRAINPOOL_P512R1,
         }
     }
 
@@ -43,16 +71,28 @@ impl EcdsaAlgorithm {
             Nid::SECP256K1 => ES256K,
             Nid::SECP384R1 => ES384,
             Nid::SECP521R1 => ES512,
+            #[cfg(feature = "brainpool")]
+            Nid::BRAINPOOL_P256R1 => BP256R1,
+            #[cfg(feature = "brainpool")]
+            Nid::BRAINPOOL_P384R1 => BP384R1,
+            #[cfg(feature = "brainpool")]
+            Nid::BRAINPOOL_P512R1 => BP512R1,
             _ => return Err(Error::UnsupportedOrInvalidKey),
         })
     }
 
-    fn digest(self) -> MessageDigest {
+    pub(crate) fn digest(self) -> MessageDigest {
         use EcdsaAlgorithm::*;
         match self {
             ES256 | ES256K => MessageDigest::sha256(),
             ES384 => MessageDigest::sha384(),
             ES512 => MessageDigest::sha512(),
+            #[cfg(feature = "brainpool")]
+            BP256R1 => MessageDigest::sha256(),
+            #[cfg(feature = "brainpool")]
+            BP384R1 => MessageDigest::sha384(),
+            #[cfg(feature = "brainpool")]
+            BP512R1 => MessageDigest::sha512(),
         }
     }
 
@@ -64,9 +104,32 @@ impl EcdsaAlgorithm {
             ES256K => "ES256K",
             ES384 => "ES384",
             ES512 => "ES512",
+            #[cfg(feature = "brainpool")]
+            BP256R1 => "BP256R1",
+            #[cfg(feature = "brainpool")]
+            BP384R1 => "BP384R1",
+            #[cfg(feature = "brainpool")]
+            BP512R1 => "BP512R1",
         }
     }
 
+    pub fn from_name(name: &str) -> Result<Self> {
+        use EcdsaAlgorithm::*;
+        Ok(match name {
+            "ES256" => ES256,
+            "ES256K" => ES256K,
+            "ES384" => ES384,
+            "ES512" => ES512,
+            #[cfg(feature = "brainpool")]
+            "BP256R1" => BP256R1,
+            #[cfg(feature = "brainpool")]
+            "BP384R1" => BP384R1,
+            #[cfg(feature = "brainpool")]
+            "BP512R1" => BP512R1,
+            _ => return Err(Error::UnsupportedOrInvalidKey),
+        })
+    }
+
     #[inline]
     pub fn curve_name(self) -> &'static str {
         use EcdsaAlgorithm::*;
@@ -75,6 +138,12 @@ impl EcdsaAlgorithm {
             ES256K => "secp256k1",
             ES384 => "P-384",
             ES512 => "P-521",
+            #[cfg(feature = "brainpool")]
+            BP256R1 => "brainpoolP256r1",
+            #[cfg(feature = "brainpool")]
+            BP384R1 => "brainpoolP384r1",
+            #[cfg(feature = "brainpool")]
+            BP512R1 => "brainpoolP512r1",
         }
     }
 
@@ -86,6 +155,12 @@ impl EcdsaAlgorithm {
             "secp256k1" => ES256K,
             "P-384" => ES384,
             "P-521" => ES512,
+            #[cfg(feature = "brainpool")]
+            "brainpoolP256r1" => BP256R1,
+            #[cfg(feature = "brainpool")]
+            "brainpoolP384r1" => BP384R1,
+            #[cfg(feature = "brainpool")]
+            "brainpoolP512r1" => BP512R1,
             _ => return Err(Error::UnsupportedOrInvalidKey),
         })
     }
@@ -97,10 +172,18 @@ impl EcdsaAlgorithm {
             ES256 | ES256K => 64,
             ES384 => 96,
             ES512 => 132,
+            #[cfg(feature = "brainpool")]
+            BP256R1 => 64,
+            #[cfg(feature = "brainpool")]
+            BP384R1 => 96,
+            #[cfg(feature = "brainpool")]
+            BP512R1 => 128,
         }
     }
 }
 
+impl_algorithm_name!(EcdsaAlgorithm);
+
 #[derive(Debug, Clone)]
 pub struct EcdsaPrivateKey {
     private_key: PKey<Private>,
@@ -108,6 +191,7 @@ pub struct EcdsaPrivateKey {
 }
 
 impl EcdsaPrivateKey {
+    #[cfg(feature = "sign")]
     pub fn generate(algorithm: EcdsaAlgorithm) -> Result<Self> {
         let ec_key = EcKey::generate(EcGroup::from_curve_name(algorithm.curve())?.as_ref())?;
         Ok(Self {
@@ -116,6 +200,17 @@ impl EcdsaPrivateKey {
         })
     }
 
+    /// Generate a key together with a `kid` for publishing it: the base64url
+    /// encoded RFC 7638 SHA-256 thumbprint of its public JWK.
+    ///
+    /// Use `WithKid::new` to sign with the returned `kid`.
+    #[cfg(feature = "sign")]
+    pub fn generate_with_kid(algorithm: EcdsaAlgorithm) -> Result<(Self, String)> {
+        let k = Self::generate(algorithm)?;
+        let kid = k.public_key_to_jwk()?.get_thumbprint_sha256_base64()?;
+        Ok((k, kid))
+    }
+
     pub(crate) fn from_pkey(pk: PKey<Private>) -> Result<Self> {
         pk.ec_key()?.check_key()?;
         let curve = pk
@@ -160,6 +255,22 @@ impl EcdsaPrivateKey {
         Self::from_pkey(pk)
     }
 
+    /// Like `from_pem`, but fails with `Error::UnexpectedKeyAlgorithm` if the
+    /// curve of the key is not the one of `algorithm`, e.g. a P-384 key when
+    /// `ES256` is expected.
+    pub fn from_pem_expecting(pem: &[u8], algorithm: EcdsaAlgorithm) -> Result<Self> {
+        let k = Self::from_pem(pem)?;
+        if k.algorithm != algorithm {
+            return Err(Error::UnexpectedKeyAlgorithm(format!(
+                "key is {}, expected {}",
+                k.algorithm.name(),
+                algorithm.name()
+            )));
+        }
+        Ok(k)
+    }
+
+    #[cfg(feature = "sign")]
     pub fn private_key_to_pem_pkcs8(&self) -> Result<String> {
         Ok(String::from_utf8(
             self.private_key.private_key_to_pem_pkcs8()?,
@@ -184,8 +295,19 @@ impl EcdsaPrivateKey {
         Ok((x, y))
     }
 
+    /// Public key as an uncompressed SEC1 point, i.e. `0x04 || x || y`.
+    pub fn public_key_to_sec1(&self) -> Result<Vec<u8>> {
+        let (x, y) = self.coordinates()?;
+        Ok(to_sec1(&x, &y))
+    }
+
+    /// Private scalar. Always padded to the full size.
     pub fn d(&self) -> Result<Vec<u8>> {
-        Ok(self.private_key.ec_key()?.private_key().to_vec())
+        Ok(self
+            .private_key
+            .ec_key()?
+            .private_key()
+            .to_vec_padded((self.algorithm.len() / 2) as i32)?)
     }
 }
 
@@ -195,6 +317,7 @@ impl PublicKeyToJwk for EcdsaPrivateKey {
         Ok(Jwk {
             kty: "EC".into(),
             use_: Some("sig".into()),
+            alg: Some(self.algorithm.name().into()),
             crv: Some(self.algorithm.curve_name().into()),
             x: Some(base64::encode_config(&x, url_safe_trailing_bits())),
             y: Some(base64::encode_config(&y, url_safe_trailing_bits())),
@@ -203,6 +326,7 @@ impl PublicKeyToJwk for EcdsaPrivateKey {
     }
 }
 
+#[cfg(feature = "sign")]
 impl PrivateKeyToJwk for EcdsaPrivateKey {
     fn private_key_to_jwk(&self) -> Result<Jwk> {
         let (x, y) = self.coordinates()?;
@@ -219,6 +343,14 @@ impl PrivateKeyToJwk for EcdsaPrivateKey {
     }
 }
 
+fn to_sec1(x: &[u8], y: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(1 + x.len() + y.len());
+    out.push(4);
+    out.extend_from_slice(x);
+    out.extend_from_slice(y);
+    out
+}
+
 fn pad_left(v: &mut Vec<u8>, len: usize) {
     debug_assert!(v.len() <= len);
     if v.len() == len {
@@ -240,7 +372,7 @@ fn test_pad_left() {
     assert_eq!(v, [0, 0, 0, 0, 0, 5, 6, 7]);
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EcdsaPublicKey {
     public_key: PKey<Public>,
     algorithm: EcdsaAlgorithm,
@@ -268,6 +400,11 @@ impl EcdsaPublicKey {
         Self::from_pkey(pk)
     }
 
+    #[inline]
+    pub fn algorithm(&self) -> EcdsaAlgorithm {
+        self.algorithm
+    }
+
     pub fn to_pem(&self) -> Result<String> {
         Ok(String::from_utf8(self.public_key.public_key_to_pem()?)?)
     }
@@ -298,6 +435,40 @@ impl EcdsaPublicKey {
             algorithm,
         })
     }
+
+    /// Uncompressed SEC1 point, i.e. `0x04 || x || y`. Inverse of `from_sec1`.
+    pub fn to_sec1(&self) -> Result<Vec<u8>> {
+        let (x, y) = self.coordinates()?;
+        Ok(to_sec1(&x, &y))
+    }
+
+    /// From an uncompressed SEC1 point, i.e. `0x04 || x || y`.
+    pub fn from_sec1(bytes: &[u8], algorithm: EcdsaAlgorithm) -> Result<Self> {
+        match bytes.split_first() {
+            Some((4, xy)) if xy.len() == algorithm.len() => {
+                let (x, y) = xy.split_at(algorithm.len() / 2);
+                Self::from_coordinates(x, y, algorithm)
+            }
+            _ => Err(Error::UnsupportedOrInvalidKey),
+        }
+    }
+}
+
+/// Compares the key material.
+impl PartialEq for EcdsaPublicKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.public_key.public_eq(&other.public_key)
+    }
+}
+
+impl Eq for EcdsaPublicKey {}
+
+/// Hashes the uncompressed SEC1 point, consistent with `PartialEq` whatever
+/// encoding the key was loaded from.
+impl std::hash::Hash for EcdsaPublicKey {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.to_sec1().ok().hash(state);
+    }
 }
 
 impl PublicKeyToJwk for EcdsaPublicKey {
@@ -306,6 +477,7 @@ impl PublicKeyToJwk for EcdsaPublicKey {
         Ok(Jwk {
             kty: "EC".into(),
             use_: Some("sig".into()),
+            alg: Some(self.algorithm.name().into()),
             crv: Some(self.algorithm.curve_name().into()),
             x: Some(base64::encode_config(&x, url_safe_trailing_bits())),
             y: Some(base64::encode_config(&y, url_safe_trailing_bits())),
@@ -314,6 +486,7 @@ impl PublicKeyToJwk for EcdsaPublicKey {
     }
 }
 
+#[cfg(feature = "sign")]
 impl SigningKey for EcdsaPrivateKey {
     fn sign(&self, v: &[u8]) -> Result<SmallVec<[u8; 64]>> {
         let hash = hash(self.algorithm.digest(), v)?;
@@ -322,21 +495,7 @@ impl SigningKey for EcdsaPrivateKey {
         // No need to parse the ASN.1 DER encoded signature.
         let sig = EcdsaSig::sign(&hash, self.private_key.ec_key()?.as_ref())?;
 
-        let sig_len = self.algorithm.len();
-        let mut out = smallvec![0u8; sig_len];
-
-        let r = sig.r();
-        let r_len = r.num_bytes() as usize;
-        debug_assert!(r_len <= sig_len / 2);
-
-        let s = sig.s();
-        let s_len = s.num_bytes() as usize;
-        debug_assert!(s_len <= sig_len / 2);
-
-        unsafe { BN_bn2bin(r.as_ptr(), out[sig_len / 2 - r_len..].as_mut_ptr()) };
-        unsafe { BN_bn2bin(s.as_ptr(), out[sig_len - s_len..].as_mut_ptr()) };
-
-        Ok(out)
+        fixed_signature(self.algorithm, &sig)
     }
 
     fn alg(&self) -> &'static str {
@@ -344,6 +503,34 @@ impl SigningKey for EcdsaPrivateKey {
     }
 }
 
+/// Convert an ASN.1 DER encoded ECDSA signature to the fixed length `r || s`
+/// form used by JWS.
+///
+/// Returns `None` if `alg` is not an ECDSA algorithm, or `sig` already has
+/// the fixed length or is not valid DER.
+pub(crate) fn der_signature_to_fixed(alg: &str, sig: &[u8]) -> Option<Vec<u8>> {
+    let alg = EcdsaAlgorithm::from_name(alg).ok()?;
+    if sig.len() == alg.len() {
+        return None;
+    }
+    let sig = EcdsaSig::from_der(sig).ok()?;
+    fixed_signature(alg, &sig).ok().map(|sig| sig.to_vec())
+}
+
+/// `r || s`, each left padded with zero bytes to half of `alg.len()`.
+///
+/// Fails with `Error::UnsupportedOrInvalidKey` if `r` or `s` is longer than
+/// that, which is only possible with a malformed key or signature.
+fn fixed_signature(alg: EcdsaAlgorithm, sig: &EcdsaSigRef) -> Result<SmallVec<[u8; 64]>> {
+    let half_len = alg.len() / 2;
+    if sig.r().num_bytes() as usize > half_len || sig.s().num_bytes() as usize > half_len {
+        return Err(Error::UnsupportedOrInvalidKey);
+    }
+    let mut out: SmallVec<[u8; 64]> = sig.r().to_vec_padded(half_len as i32)?.into();
+    out.extend_from_slice(&sig.s().to_vec_padded(half_len as i32)?);
+    Ok(out)
+}
+
 fn ecdsa_verify<T: HasPublic>(
     alg: EcdsaAlgorithm,
     k: &PKeyRef<T>,
@@ -385,12 +572,68 @@ impl VerificationKey for EcdsaPublicKey {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "sign"))]
 mod tests {
     use crate::{rsa::RsaAlgorithm, SomePrivateKey};
 
     use super::*;
 
+    #[test]
+    fn algorithm_serde() -> Result<()> {
+        #[derive(serde::Deserialize, serde::Serialize)]
+        struct Config {
+            alg: EcdsaAlgorithm,
+            fallback: RsaAlgorithm,
+        }
+
+        let config: Config = serde_json::from_str(r#"{"alg":"ES256K","fallback":"PS384"}"#)?;
+        assert_eq!(config.alg, EcdsaAlgorithm::ES256K);
+        assert_eq!(config.fallback, RsaAlgorithm::PS384);
+        assert_eq!(
+            serde_json::to_string(&config)?,
+            r#"{"alg":"ES256K","fallback":"PS384"}"#
+        );
+
+        let e = serde_json::from_str::<Config>(r#"{"alg":"es256","fallback":"PS384"}"#)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(e.contains("unknown algorithm `es256`, expected one of ES256, ES256K"));
+
+        assert_eq!("ES384".parse::<EcdsaAlgorithm>()?, EcdsaAlgorithm::ES384);
+        assert_eq!(RsaAlgorithm::RS512.to_string(), "RS512");
+        Ok(())
+    }
+
+    #[test]
+    fn from_pem_expecting() -> Result<()> {
+        let pem = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES384)?.private_key_to_pem_pkcs8()?;
+        EcdsaPrivateKey::from_pem_expecting(pem.as_bytes(), EcdsaAlgorithm::ES384)?;
+        let e =
+            EcdsaPrivateKey::from_pem_expecting(pem.as_bytes(), EcdsaAlgorithm::ES256).unwrap_err();
+        assert!(matches!(e, Error::UnexpectedKeyAlgorithm(_)));
+        assert_eq!(
+            e.to_string(),
+            "unexpected key algorithm: key is ES384, expected ES256"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn generate_with_kid() -> Result<()> {
+        let (k, kid) = EcdsaPrivateKey::generate_with_kid(EcdsaAlgorithm::ES384)?;
+        assert_eq!(kid, k.public_key_to_jwk()?.get_thumbprint_sha256_base64()?);
+        assert_eq!(kid.len(), 43);
+
+        let (k1, kid1) = EcdsaPrivateKey::generate_with_kid(EcdsaAlgorithm::ES384)?;
+        assert_ne!(kid, kid1);
+        assert_eq!(
+            kid1,
+            k1.public_key_to_jwk()?.get_thumbprint_sha256_base64()?
+        );
+        Ok(())
+    }
+
     #[test]
     fn conversion() -> Result<()> {
         let k = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
@@ -427,6 +670,8 @@ mod tests {
 
         EcdsaPublicKey::from_coordinates(&x, &y, EcdsaAlgorithm::ES256)?;
 
+        // Irrefutable if ECDSA is the only enabled family.
+        #[allow(irrefutable_let_patterns)]
         if let SomePrivateKey::Ecdsa(k1) = k
             .private_key_to_jwk()?
             .to_signing_key(RsaAlgorithm::PS256)?
@@ -443,13 +688,142 @@ mod tests {
     }
 
     #[test]
-    fn sign_verify() -> Result<()> {
+    fn from_sec1() -> Result<()> {
+        let k = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES384)?;
+        let (x, y) = k.coordinates()?;
+        let mut sec1 = vec![4u8];
+        sec1.extend_from_slice(&x);
+        sec1.extend_from_slice(&y);
+
+        let pk = EcdsaPublicKey::from_sec1(&sec1, EcdsaAlgorithm::ES384)?;
+        assert_eq!(pk.coordinates()?, (x, y));
+        let sig = k.sign(b"...")?;
+        pk.verify(b"...", &sig, "ES384")?;
+
+        assert!(EcdsaPublicKey::from_sec1(&sec1, EcdsaAlgorithm::ES256).is_err());
+        assert!(EcdsaPublicKey::from_sec1(&sec1[..sec1.len() - 1], EcdsaAlgorithm::ES384).is_err());
+        sec1[0] = 2;
+        assert!(EcdsaPublicKey::from_sec1(&sec1, EcdsaAlgorithm::ES384).is_err());
+        assert!(EcdsaPublicKey::from_sec1(&[], EcdsaAlgorithm::ES384).is_err());
+        Ok(())
+    }
+
+    /// Generate keys until `matches` holds, e.g. to find one with a leading
+    /// zero byte in a coordinate or `d`. About 1 in 128 P-256 coordinates has
+    /// one, while the top byte of a P-521 coordinate is 0 or 1.
+    fn find_key(
+        alg: EcdsaAlgorithm,
+        matches: impl Fn(&EcdsaPrivateKey) -> bool,
+    ) -> EcdsaPrivateKey {
+        (0..10000)
+            .map(|_| EcdsaPrivateKey::generate(alg).unwrap())
+            .find(matches)
+            .expect("no matching key")
+    }
+
+    /// A coordinate has a leading zero byte.
+    fn has_leading_zero(k: &EcdsaPrivateKey) -> bool {
+        let (x, y) = k.coordinates().unwrap();
+        x[0] == 0 || y[0] == 0
+    }
+
+    #[test]
+    fn sec1_coordinates_leading_zero() -> Result<()> {
+        let k = find_key(EcdsaAlgorithm::ES256, has_leading_zero);
+
+        let (x, y) = k.coordinates()?;
+        assert_eq!((x.len(), y.len()), (32, 32));
+
+        let sec1 = k.public_key_to_sec1()?;
+        assert_eq!(sec1.len(), 65);
+        let pk = EcdsaPublicKey::from_sec1(&sec1, EcdsaAlgorithm::ES256)?;
+        assert_eq!(pk.coordinates()?, (x, y));
+        assert_eq!(pk.to_sec1()?, sec1);
+        Ok(())
+    }
+
+    #[test]
+    fn jwk_coordinates_padded() -> Result<()> {
+        for (alg, len) in [
+            (EcdsaAlgorithm::ES256, 32),
+            (EcdsaAlgorithm::ES384, 48),
+            (EcdsaAlgorithm::ES512, 66),
+        ] {
+            let k = find_key(alg, has_leading_zero);
+            let jwk = k.public_key_to_jwk()?;
+            let x = base64::decode_config(jwk.x.as_ref().unwrap(), url_safe_trailing_bits())?;
+            let y = base64::decode_config(jwk.y.as_ref().unwrap(), url_safe_trailing_bits())?;
+            assert_eq!((x.len(), y.len()), (len, len));
+            assert!(x[0] == 0 || y[0] == 0);
+            assert_eq!(k.coordinates()?, (x, y));
+
+            let jwk = k.private_key_to_jwk()?;
+            let x = base64::decode_config(jwk.x.as_ref().unwrap(), url_safe_trailing_bits())?;
+            let y = base64::decode_config(jwk.y.as_ref().unwrap(), url_safe_trailing_bits())?;
+            assert_eq!((x.len(), y.len()), (len, len));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn es512_short_coordinate() -> Result<()> {
+        let k = find_key(EcdsaAlgorithm::ES512, |k| {
+            let (x, y) = k.coordinates().unwrap();
+            x[0] == 0 && y[0] != 0
+        });
+        let (x, y) = k.coordinates()?;
+        assert_eq!((x.len(), y.len()), (66, 66));
+
+        let pk = EcdsaPublicKey::from_coordinates(&x[1..], &y, EcdsaAlgorithm::ES512)?;
+        assert_eq!(pk.coordinates()?, (x.clone(), y.clone()));
+
+        let jwk = pk.public_key_to_jwk()?;
+        let jwk_x = base64::decode_config(jwk.x.as_ref().unwrap(), url_safe_trailing_bits())?;
+        let jwk_y = base64::decode_config(jwk.y.as_ref().unwrap(), url_safe_trailing_bits())?;
+        assert_eq!((jwk_x, jwk_y), (x.clone(), y.clone()));
+
+        // A JWK with the leading zero stripped, as some libraries produce.
+        let stripped = Jwk {
+            x: Some(base64::encode_config(&x[1..], url_safe_trailing_bits())),
+            ..jwk
+        };
+        let sig = k.sign(b"...")?;
+        stripped
+            .to_verification_key()?
+            .verify(b"...", &sig, "ES512")?;
+        Ok(())
+    }
+
+    #[test]
+    fn private_jwk_round_trip() -> Result<()> {
         for alg in [
             EcdsaAlgorithm::ES256,
             EcdsaAlgorithm::ES256K,
             EcdsaAlgorithm::ES384,
             EcdsaAlgorithm::ES512,
         ] {
+            // Find a key with a short `d` to check padding.
+            let k = find_key(alg, |k| {
+                k.private_key.ec_key().unwrap().private_key().num_bytes() as usize * 2 < alg.len()
+            });
+            let jwk = k.private_key_to_jwk()?;
+            let d = base64::decode_config(jwk.d.as_ref().unwrap(), url_safe_trailing_bits())?;
+            assert_eq!(d.len(), alg.len() / 2);
+            assert!(k.public_key_to_jwk()?.d.is_none());
+
+            let k1 = jwk.to_signing_key(RsaAlgorithm::RS256)?;
+            assert_eq!(k1.alg(), alg.name());
+            assert_eq!(
+                serde_json::to_value(&jwk)?,
+                serde_json::to_value(&k1.private_key_to_jwk()?)?
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn sign_verify() -> Result<()> {
+        for &alg in EcdsaAlgorithm::all() {
             let k = EcdsaPrivateKey::generate(alg)?;
             let (x, y) = k.coordinates()?;
             let pk = EcdsaPublicKey::from_coordinates(&x, &y, alg)?;
@@ -462,4 +836,65 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn fixed_signature_padding() -> Result<()> {
+        // Short `r` and `s` are more likely with larger curves: with P-521 the
+        // top byte of a 66 byte half is usually zero.
+        for alg in [EcdsaAlgorithm::ES256, EcdsaAlgorithm::ES512] {
+            let k = EcdsaPrivateKey::generate(alg)?;
+            for i in 0..200u32 {
+                let v = i.to_be_bytes();
+                let sig = k.sign(&v)?;
+                assert_eq!(sig.len(), alg.len());
+                k.verify(&v, &sig, alg.name())?;
+            }
+        }
+
+        let alg = EcdsaAlgorithm::ES256;
+        let sig = EcdsaSig::from_private_components(BigNum::from_u32(1)?, BigNum::from_u32(2)?)?;
+        let fixed = fixed_signature(alg, &sig)?;
+        assert_eq!(fixed.len(), 64);
+        assert_eq!((fixed[31], fixed[63]), (1, 2));
+        assert!(fixed[..31].iter().chain(&fixed[32..63]).all(|b| *b == 0));
+
+        // 33 bytes is too long for P-256.
+        let too_long = BigNum::from_slice(&[1u8; 33])?;
+        let sig = EcdsaSig::from_private_components(too_long, BigNum::from_u32(2)?)?;
+        assert!(matches!(
+            fixed_signature(alg, &sig),
+            Err(Error::UnsupportedOrInvalidKey)
+        ));
+        let sig = EcdsaSig::from_private_components(
+            BigNum::from_u32(1)?,
+            BigNum::from_slice(&[1u8; 33])?,
+        )?;
+        assert!(fixed_signature(alg, &sig).is_err());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "brainpool")]
+    #[test]
+    fn sign_verify_brainpool() -> Result<()> {
+        for alg in [
+            EcdsaAlgorithm::BP256R1,
+            EcdsaAlgorithm::BP384R1,
+            EcdsaAlgorithm::BP512R1,
+        ] {
+            let k = EcdsaPrivateKey::generate(alg)?;
+            let pk = k.public_key_to_jwk()?.to_verification_key()?;
+            let sig = k.sign(b"...")?;
+            assert_eq!(sig.len(), alg.len());
+            assert!(k.verify(b"...", &sig, alg.name()).is_ok());
+            assert!(pk.verify(b"...", &sig, alg.name()).is_ok());
+            assert!(pk.verify(b"....", &sig, alg.name()).is_err());
+            assert!(pk.verify(b"...", &sig, "ES256").is_err());
+
+            let pem = k.public_key_to_pem()?;
+            let pk = EcdsaPublicKey::from_pem(pem.as_bytes())?;
+            assert!(pk.verify(b"...", &sig, alg.name()).is_ok());
+        }
+        Ok(())
+    }
 }
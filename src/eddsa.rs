@@ -1,17 +1,17 @@
-use std::ptr;
+use std::convert::TryInto;
 
-use foreign_types::ForeignType;
+#[cfg(feature = "sign")]
+use openssl::sign::Signer;
 use openssl::{
-    error::ErrorStack,
-    pkey::{PKey, Private, Public},
-    sign::{Signer, Verifier},
+    pkey::{Id, PKey, Private, Public},
+    sign::Verifier,
 };
+#[cfg(feature = "sign")]
 use smallvec::SmallVec;
 
-use crate::{
-    jwk::Jwk, url_safe_trailing_bits, Error, PrivateKeyToJwk, PublicKeyToJwk, Result, SigningKey,
-    VerificationKey,
-};
+use crate::{jwk::Jwk, url_safe_trailing_bits, Error, PublicKeyToJwk, Result, VerificationKey};
+#[cfg(feature = "sign")]
+use crate::{PrivateKeyToJwk, SigningKey};
 
 #[derive(Debug, Clone)]
 pub struct Ed25519PrivateKey {
@@ -19,30 +19,20 @@ pub struct Ed25519PrivateKey {
 }
 
 impl Ed25519PrivateKey {
+    #[cfg(feature = "sign")]
     pub fn generate() -> Result<Self> {
         let pkey = PKey::generate_ed25519()?;
         Ok(Self { private_key: pkey })
     }
 
     pub fn from_bytes(b: &[u8]) -> Result<Self> {
-        let pkey = unsafe {
-            openssl_sys::EVP_PKEY_new_raw_private_key(
-                openssl_sys::EVP_PKEY_ED25519,
-                ptr::null_mut(),
-                b.as_ptr(),
-                b.len(),
-            )
-        };
-        if pkey.is_null() {
-            return Err(ErrorStack::get().into());
-        }
         Ok(Self {
-            private_key: unsafe { PKey::from_ptr(pkey) },
+            private_key: PKey::private_key_from_raw_bytes(b, Id::ED25519)?,
         })
     }
 
     pub(crate) fn from_pkey(pk: PKey<Private>) -> Result<Self> {
-        if pk.id() != openssl::pkey::Id::ED25519 {
+        if pk.id() != Id::ED25519 {
             return Err(Error::UnsupportedOrInvalidKey);
         }
         Ok(Self { private_key: pk })
@@ -54,35 +44,20 @@ impl Ed25519PrivateKey {
     }
 
     pub fn private_key_bytes(&self) -> Result<[u8; 32]> {
-        let mut out = [0u8; 32];
-        let r = unsafe {
-            openssl_sys::EVP_PKEY_get_raw_private_key(
-                self.private_key.as_ptr(),
-                out.as_mut_ptr(),
-                &mut out.len(),
-            )
-        };
-        if r == 0 {
-            return Err(ErrorStack::get().into());
-        }
-        Ok(out)
+        self.private_key
+            .raw_private_key()?
+            .try_into()
+            .map_err(|_| Error::UnsupportedOrInvalidKey)
     }
 
     pub fn public_key_bytes(&self) -> Result<[u8; 32]> {
-        let mut out = [0u8; 32];
-        let r = unsafe {
-            openssl_sys::EVP_PKEY_get_raw_public_key(
-                self.private_key.as_ptr(),
-                out.as_mut_ptr(),
-                &mut out.len(),
-            )
-        };
-        if r == 0 {
-            return Err(ErrorStack::get().into());
-        }
-        Ok(out)
+        self.private_key
+            .raw_public_key()?
+            .try_into()
+            .map_err(|_| Error::UnsupportedOrInvalidKey)
     }
 
+    #[cfg(feature = "sign")]
     pub fn private_key_to_pem_pkcs8(&self) -> Result<String> {
         Ok(String::from_utf8(
             self.private_key.private_key_to_pem_pkcs8()?,
@@ -100,12 +75,13 @@ impl PublicKeyToJwk for Ed25519PrivateKey {
         Ok(Jwk {
             kty: "OKP".into(),
             crv: Some("Ed25519".into()),
-            x: Some(base64::encode_config(&bytes, url_safe_trailing_bits())),
+            x: Some(base64::encode_config(bytes, url_safe_trailing_bits())),
             ..Jwk::default()
         })
     }
 }
 
+#[cfg(feature = "sign")]
 impl PrivateKeyToJwk for Ed25519PrivateKey {
     fn private_key_to_jwk(&self) -> Result<Jwk> {
         let d = self.private_key_bytes()?;
@@ -113,21 +89,21 @@ impl PrivateKeyToJwk for Ed25519PrivateKey {
         Ok(Jwk {
             kty: "OKP".into(),
             crv: Some("Ed25519".into()),
-            d: Some(base64::encode_config(&d, url_safe_trailing_bits())),
-            x: Some(base64::encode_config(&x, url_safe_trailing_bits())),
+            d: Some(base64::encode_config(d, url_safe_trailing_bits())),
+            x: Some(base64::encode_config(x, url_safe_trailing_bits())),
             ..Jwk::default()
         })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Ed25519PublicKey {
     public_key: PKey<Public>,
 }
 
 impl Ed25519PublicKey {
     pub(crate) fn from_pkey(pkey: PKey<Public>) -> Result<Self> {
-        if pkey.id() != openssl::pkey::Id::ED25519 {
+        if pkey.id() != Id::ED25519 {
             return Err(Error::UnsupportedOrInvalidKey);
         }
         Ok(Self { public_key: pkey })
@@ -139,19 +115,8 @@ impl Ed25519PublicKey {
     }
 
     pub fn from_bytes(b: &[u8]) -> Result<Self> {
-        let pkey = unsafe {
-            openssl_sys::EVP_PKEY_new_raw_public_key(
-                openssl_sys::EVP_PKEY_ED25519,
-                ptr::null_mut(),
-                b.as_ptr(),
-                b.len(),
-            )
-        };
-        if pkey.is_null() {
-            return Err(ErrorStack::get().into());
-        }
         Ok(Self {
-            public_key: unsafe { PKey::from_ptr(pkey) },
+            public_key: PKey::public_key_from_raw_bytes(b, Id::ED25519)?,
         })
     }
 
@@ -160,18 +125,26 @@ impl Ed25519PublicKey {
     }
 
     pub fn to_bytes(&self) -> Result<[u8; 32]> {
-        let mut out = [0u8; 32];
-        let r = unsafe {
-            openssl_sys::EVP_PKEY_get_raw_public_key(
-                self.public_key.as_ptr(),
-                out.as_mut_ptr(),
-                &mut out.len(),
-            )
-        };
-        if r == 0 {
-            return Err(ErrorStack::get().into());
-        }
-        Ok(out)
+        self.public_key
+            .raw_public_key()?
+            .try_into()
+            .map_err(|_| Error::UnsupportedOrInvalidKey)
+    }
+}
+
+/// Compares the key material.
+impl PartialEq for Ed25519PublicKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.public_key.public_eq(&other.public_key)
+    }
+}
+
+impl Eq for Ed25519PublicKey {}
+
+/// Hashes the raw public key, consistent with `PartialEq`.
+impl std::hash::Hash for Ed25519PublicKey {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.to_bytes().ok().hash(state);
     }
 }
 
@@ -181,12 +154,13 @@ impl PublicKeyToJwk for Ed25519PublicKey {
         Ok(Jwk {
             kty: "OKP".into(),
             crv: Some("Ed25519".into()),
-            x: Some(base64::encode_config(&bytes, url_safe_trailing_bits())),
+            x: Some(base64::encode_config(bytes, url_safe_trailing_bits())),
             ..Jwk::default()
         })
     }
 }
 
+#[cfg(feature = "sign")]
 impl SigningKey for Ed25519PrivateKey {
     fn sign(&self, v: &[u8]) -> Result<SmallVec<[u8; 64]>> {
         let mut signer = Signer::new_without_digest(self.private_key.as_ref())?;
@@ -233,7 +207,7 @@ impl VerificationKey for Ed25519PublicKey {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "sign"))]
 mod tests {
     use openssl::{
         ec::{EcGroup, EcKey},
@@ -274,6 +248,8 @@ mod tests {
 
         assert_eq!(pk_pem, pk_pem1);
 
+        // Irrefutable if EdDSA is the only enabled family.
+        #[allow(irrefutable_let_patterns)]
         if let SomePrivateKey::Ed25519(k1) = k
             .private_key_to_jwk()?
             .to_signing_key(RsaAlgorithm::PS256)?
@@ -289,6 +265,22 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn private_jwk_round_trip() -> Result<()> {
+        let k = Ed25519PrivateKey::generate()?;
+        let jwk = k.private_key_to_jwk()?;
+        assert_eq!(jwk.crv.as_deref(), Some("Ed25519"));
+        assert!(jwk.x.is_some());
+        assert!(k.public_key_to_jwk()?.d.is_none());
+
+        let k1 = jwk.to_signing_key(RsaAlgorithm::RS256)?;
+        assert_eq!(
+            serde_json::to_value(&jwk)?,
+            serde_json::to_value(&k1.private_key_to_jwk()?)?
+        );
+        Ok(())
+    }
+
     #[test]
     fn sign_verify() -> Result<()> {
         let k = Ed25519PrivateKey::generate()?;
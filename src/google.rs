@@ -0,0 +1,171 @@
+//! Google ID tokens, e.g. of "Sign in with Google".
+
+#[cfg(feature = "remote-jwks")]
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+#[cfg(feature = "remote-jwks")]
+use crate::{jwk::RemoteJwksVerifier, Result, Verified};
+use crate::{Error, VerifyOptions};
+
+/// Both issuers Google uses for ID tokens.
+pub const ISSUERS: [&str; 2] = ["accounts.google.com", "https://accounts.google.com"];
+
+/// Google's JWK Set for ID tokens.
+pub const JWKS_URL: &str = "https://www.googleapis.com/oauth2/v3/certs";
+
+/// Google specific claims of ID tokens, use as `ExtraClaims`.
+#[non_exhaustive]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GoogleClaims {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+    /// Hosted domain of a Google Workspace account.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hd: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub picture: Option<String>,
+    /// Other claims.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl GoogleClaims {
+    /// The email address, only if Google verified it.
+    pub fn verified_email(&self) -> Option<&str> {
+        match self.email_verified {
+            Some(true) => self.email.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// Verify options for Google ID tokens of `client_id`: `iss` is one of
+/// `ISSUERS` and `aud` is `client_id`.
+///
+/// With `hosted_domain`, `hd` must be that domain, i.e. only accounts of that
+/// Google Workspace are accepted.
+pub fn verify_options(client_id: impl Into<String>, hosted_domain: Option<&str>) -> VerifyOptions {
+    let mut options = VerifyOptions::new();
+    options
+        .set_issuers(ISSUERS.iter().map(|iss| iss.to_string()).collect())
+        .add_aud(client_id);
+    if let Some(hd) = hosted_domain {
+        let hd = hd.to_string();
+        options.add_validator(move |claims| {
+            if claims.extra.get("hd").and_then(Value::as_str) == Some(&*hd) {
+                Ok(())
+            } else {
+                Err(Error::InvalidClaim(format!("hd is not {}", hd)))
+            }
+        });
+    }
+    options
+}
+
+/// Verifies Google ID tokens with keys from `JWKS_URL`, see `verify_options`.
+#[cfg(feature = "remote-jwks")]
+pub struct GoogleIdTokenVerifier {
+    jwks: RemoteJwksVerifier,
+    options: VerifyOptions,
+}
+
+#[cfg(feature = "remote-jwks")]
+impl GoogleIdTokenVerifier {
+    pub fn new(client_id: impl Into<String>, hosted_domain: Option<&str>) -> Result<Self> {
+        let jwks =
+            RemoteJwksVerifier::new(JWKS_URL.into(), None, std::time::Duration::from_secs(300))?;
+        Ok(Self::with_jwks_verifier(jwks, client_id, hosted_domain))
+    }
+
+    /// Like `new`, but with keys from `jwks`, e.g. with a custom client.
+    pub fn with_jwks_verifier(
+        jwks: RemoteJwksVerifier,
+        client_id: impl Into<String>,
+        hosted_domain: Option<&str>,
+    ) -> Self {
+        Self {
+            jwks,
+            options: verify_options(client_id, hosted_domain),
+        }
+    }
+
+    pub async fn verify<ExtraClaims: DeserializeOwned>(
+        &self,
+        token: &str,
+    ) -> Result<Verified<ExtraClaims>> {
+        self.jwks.verify_with_options(token, &self.options).await
+    }
+}
+
+#[cfg(all(test, feature = "sign", feature = "ecdsa"))]
+mod tests {
+    use crate::{
+        ecdsa::{EcdsaAlgorithm, EcdsaPrivateKey},
+        sign, verify_with_options, HeaderAndClaims, Result,
+    };
+
+    use super::*;
+
+    #[test]
+    fn google_id_token() -> Result<()> {
+        let k = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let token = |iss: &str, hd: &str| {
+            sign(
+                HeaderAndClaims::new_dynamic()
+                    .set_iss(iss)
+                    .add_aud("123.apps.googleusercontent.com")
+                    .set_sub("110169484474386276334")
+                    .set_exp_from_now(std::time::Duration::from_secs(3600))
+                    .insert("email", "alice@example.com")
+                    .insert("email_verified", true)
+                    .insert("hd", hd)
+                    .insert("locale", "en"),
+                &k,
+            )
+        };
+
+        let options = verify_options("123.apps.googleusercontent.com", Some("example.com"));
+        for iss in ISSUERS {
+            let verified =
+                verify_with_options::<GoogleClaims>(&token(iss, "example.com")?, &k, &options)?;
+            let claims = &verified.header_and_claims().claims().extra;
+            assert_eq!(claims.hd.as_deref(), Some("example.com"));
+            assert_eq!(claims.verified_email(), Some("alice@example.com"));
+            assert_eq!(claims.extra["locale"], "en");
+        }
+
+        assert!(matches!(
+            verify_with_options::<GoogleClaims>(
+                &token("accounts.google.com", "evil.com")?,
+                &k,
+                &options
+            ),
+            Err(Error::InvalidClaim(_))
+        ));
+        assert!(matches!(
+            verify_with_options::<GoogleClaims>(
+                &token("https://accounts.google.com/", "example.com")?,
+                &k,
+                &options
+            ),
+            Err(Error::InvalidIssuer)
+        ));
+
+        let options = verify_options("456.apps.googleusercontent.com", None);
+        assert!(matches!(
+            verify_with_options::<GoogleClaims>(
+                &token("accounts.google.com", "evil.com")?,
+                &k,
+                &options
+            ),
+            Err(Error::InvalidAudience)
+        ));
+        Ok(())
+    }
+}
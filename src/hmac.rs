@@ -1,7 +1,17 @@
-use openssl::{hash::MessageDigest, memcmp, pkey::PKey, rand::rand_bytes, sign::Signer};
+use std::collections::HashMap;
+
+#[cfg(feature = "sign")]
+use openssl::rand::rand_bytes;
+use openssl::{hash::MessageDigest, memcmp, pkey::PKey, sign::Signer};
+use serde::de::DeserializeOwned;
 use smallvec::{smallvec, SmallVec};
 
-use crate::{Error, Result, SigningKey, VerificationKey};
+#[cfg(feature = "sign")]
+use crate::SigningKey;
+use crate::{
+    decode_header, jwk::key_without_kid, url_safe_trailing_bits, verify_with_options, Error,
+    Result, VerificationKey, Verified, VerifyOptions,
+};
 
 #[non_exhaustive]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -12,7 +22,13 @@ pub enum HmacAlgorithm {
 }
 
 impl HmacAlgorithm {
-    fn name(self) -> &'static str {
+    /// All HMAC algorithms.
+    pub fn all() -> &'static [Self] {
+        use HmacAlgorithm::*;
+        &[HS256, HS384, HS512]
+    }
+
+    pub fn name(self) -> &'static str {
         use HmacAlgorithm::*;
         match self {
             HS256 => "HS256",
@@ -39,6 +55,7 @@ pub struct HmacKey {
 
 impl HmacKey {
     #[inline]
+    #[cfg(feature = "sign")]
     pub fn generate(algorithm: HmacAlgorithm) -> Result<Self> {
         let len = match algorithm {
             HmacAlgorithm::HS256 => 32,
@@ -62,14 +79,43 @@ impl HmacKey {
         }
     }
 
+    /// From a standard base64 encoded key, see `from_bytes`.
+    pub fn from_base64(k: &str, algorithm: HmacAlgorithm) -> Result<Self> {
+        Ok(Self::from_bytes(&base64::decode(k)?, algorithm))
+    }
+
+    /// From a base64url encoded key, with or without padding, see
+    /// `from_bytes`.
+    pub fn from_base64url(k: &str, algorithm: HmacAlgorithm) -> Result<Self> {
+        let k = base64::decode_config(k.trim_end_matches('='), url_safe_trailing_bits())?;
+        Ok(Self::from_bytes(&k, algorithm))
+    }
+
+    /// From a hex encoded key, see `from_bytes`.
+    ///
+    /// Fails with `Error::UnsupportedOrInvalidKey` if `k` is not valid hex.
+    pub fn from_hex(k: &str, algorithm: HmacAlgorithm) -> Result<Self> {
+        if !k.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(Error::UnsupportedOrInvalidKey);
+        }
+        let k = k
+            .as_bytes()
+            .chunks(2)
+            .map(|c| match c {
+                [_, _] => u8::from_str_radix(std::str::from_utf8(c).ok()?, 16).ok(),
+                _ => None,
+            })
+            .collect::<Option<Vec<u8>>>()
+            .ok_or(Error::UnsupportedOrInvalidKey)?;
+        Ok(Self::from_bytes(&k, algorithm))
+    }
+
     #[inline]
     pub fn serialize(&self) -> &[u8] {
         &self.k
     }
-}
 
-impl SigningKey for HmacKey {
-    fn sign(&self, v: &[u8]) -> Result<SmallVec<[u8; 64]>> {
+    fn mac(&self, v: &[u8]) -> Result<SmallVec<[u8; 64]>> {
         let pk = PKey::hmac(&self.k)?;
         let mut signer = Signer::new(self.algorithm.digest(), pk.as_ref())?;
 
@@ -77,6 +123,14 @@ impl SigningKey for HmacKey {
         signer.sign_oneshot(&mut sig, v)?;
         Ok(sig)
     }
+}
+
+#[cfg(feature = "sign")]
+impl SigningKey for HmacKey {
+    #[inline]
+    fn sign(&self, v: &[u8]) -> Result<SmallVec<[u8; 64]>> {
+        self.mac(v)
+    }
 
     #[inline]
     fn alg(&self) -> &'static str {
@@ -90,7 +144,7 @@ impl VerificationKey for HmacKey {
             return Err(Error::VerificationError);
         }
 
-        let expected = self.sign(v)?;
+        let expected = self.mac(v)?;
 
         if memcmp::eq(sig, &expected) {
             Ok(())
@@ -100,7 +154,77 @@ impl VerificationKey for HmacKey {
     }
 }
 
-#[cfg(test)]
+/// HMAC keys indexed by `kid`, e.g. the current and the previous secret
+/// during a rotation.
+///
+/// A `kid` is required by default, tokens without one fail with
+/// `Error::NoKid`.
+#[derive(Debug, Clone)]
+pub struct HmacKeySet {
+    keys: HashMap<String, HmacKey>,
+    require_kid: bool,
+}
+
+impl Default for HmacKeySet {
+    fn default() -> Self {
+        Self {
+            keys: HashMap::new(),
+            require_kid: true,
+        }
+    }
+}
+
+impl HmacKeySet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// If called with `false`, tokens without a `kid` are verified with the
+    /// only key of the set, see `JwkSetVerifier::set_require_kid`.
+    pub fn set_require_kid(&mut self, required: bool) -> &mut Self {
+        self.require_kid = required;
+        self
+    }
+
+    /// Add or replace the key of `kid`.
+    pub fn insert(&mut self, kid: impl Into<String>, key: HmacKey) -> &mut Self {
+        self.keys.insert(kid.into(), key);
+        self
+    }
+
+    /// Remove the key of `kid`, e.g. once the rotation is over.
+    pub fn remove(&mut self, kid: &str) -> Option<HmacKey> {
+        self.keys.remove(kid)
+    }
+
+    pub fn find(&self, kid: &str) -> Option<&HmacKey> {
+        self.keys.get(kid)
+    }
+
+    /// Decode and verify token with the key of its `kid`.
+    ///
+    /// The `alg`, `exp`, `nbf` and `iat` fields are automatically checked.
+    pub fn verify<ExtraClaims: DeserializeOwned>(
+        &self,
+        token: &str,
+    ) -> Result<Verified<ExtraClaims>> {
+        self.verify_with_options(token, &VerifyOptions::default())
+    }
+
+    pub fn verify_with_options<ExtraClaims: DeserializeOwned>(
+        &self,
+        token: &str,
+        options: &VerifyOptions,
+    ) -> Result<Verified<ExtraClaims>> {
+        let k = match decode_header(token)?.kid {
+            Some(kid) => self.find(&kid).ok_or(Error::NoKey)?,
+            None => key_without_kid(self.require_kid, self.keys.values())?,
+        };
+        verify_with_options(token, k, options)
+    }
+}
+
+#[cfg(all(test, feature = "sign"))]
 mod tests {
     use super::*;
 
@@ -115,6 +239,38 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn from_encoded() -> Result<()> {
+        let alg = HmacAlgorithm::HS256;
+        // b"\xfb\xff\x01key" is `+/8Ba2V5` in base64 and `-_8Ba2V5` in base64url.
+        let bytes = b"\xfb\xff\x01key";
+        assert_eq!(HmacKey::from_base64("+/8Ba2V5", alg)?.serialize(), bytes);
+        assert_eq!(HmacKey::from_base64url("-_8Ba2V5", alg)?.serialize(), bytes);
+        assert_eq!(HmacKey::from_hex("fbff016b6579", alg)?.serialize(), bytes);
+        assert_eq!(HmacKey::from_hex("FBFF016B6579", alg)?.serialize(), bytes);
+        assert_eq!(HmacKey::from_base64url("a2V5", alg)?.serialize(), b"key");
+        assert_eq!(
+            HmacKey::from_base64url("a2V5MQ==", alg)?.serialize(),
+            b"key1"
+        );
+
+        assert!(matches!(
+            HmacKey::from_base64("-_8Ba2V5", alg),
+            Err(Error::Decode(_))
+        ));
+        assert!(matches!(
+            HmacKey::from_base64url("+/8Ba2V5", alg),
+            Err(Error::Decode(_))
+        ));
+        for invalid in ["fbff016b657", "fbff016b65zz", "+0", "é1"] {
+            assert!(matches!(
+                HmacKey::from_hex(invalid, alg),
+                Err(Error::UnsupportedOrInvalidKey)
+            ));
+        }
+        Ok(())
+    }
+
     #[test]
     fn sign_and_verify() -> Result<()> {
         for alg in [
@@ -130,4 +286,55 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn key_set_rotation() -> Result<()> {
+        use crate::{jwk::WithKid, sign, HeaderAndClaims};
+        use serde_json::{Map, Value};
+
+        let old = HmacKey::generate(HmacAlgorithm::HS256)?;
+        let new = HmacKey::generate(HmacAlgorithm::HS256)?;
+        let old_token = sign(
+            &mut HeaderAndClaims::new_dynamic(),
+            &WithKid::new("old".into(), old.clone()),
+        )?;
+        let new_token = sign(
+            &mut HeaderAndClaims::new_dynamic(),
+            &WithKid::new("new".into(), new.clone()),
+        )?;
+
+        let mut keys = HmacKeySet::new();
+        keys.insert("old", old.clone()).insert("new", new.clone());
+        keys.verify::<Map<String, Value>>(&old_token)?;
+        keys.verify::<Map<String, Value>>(&new_token)?;
+
+        // Right secret, wrong kid.
+        let swapped = sign(
+            &mut HeaderAndClaims::new_dynamic(),
+            &WithKid::new("new".into(), old.clone()),
+        )?;
+        assert!(keys.verify::<Map<String, Value>>(&swapped).is_err());
+
+        let no_kid = sign(&mut HeaderAndClaims::new_dynamic(), &old)?;
+        assert!(matches!(
+            keys.verify::<Map<String, Value>>(&no_kid),
+            Err(Error::NoKid)
+        ));
+        keys.set_require_kid(false);
+        assert!(matches!(
+            keys.verify::<Map<String, Value>>(&no_kid),
+            Err(Error::AmbiguousKey)
+        ));
+
+        keys.remove("old");
+        assert!(matches!(
+            keys.verify::<Map<String, Value>>(&old_token),
+            Err(Error::NoKey)
+        ));
+        keys.verify::<Map<String, Value>>(&new_token)?;
+        // Only the new key is left.
+        let no_kid = sign(&mut HeaderAndClaims::new_dynamic(), &new)?;
+        keys.verify::<Map<String, Value>>(&no_kid)?;
+        Ok(())
+    }
 }
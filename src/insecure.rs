@@ -0,0 +1,110 @@
+//! Verification of unsecured tokens (`alg: none`) for local development, e.g.
+//! against an identity provider stub that does not sign its tokens.
+//!
+//! Only available with the `allow-insecure` feature. NEVER enable it in
+//! production: anyone can forge unsecured tokens.
+
+use serde::de::DeserializeOwned;
+
+use crate::{decode_payload, decode_without_verify, Error, Result, Verified, VerifyOptions};
+
+/// Accepts UNSECURED tokens, i.e. with `alg: none` and an empty signature, and
+/// only validates their claims.
+///
+/// Signed tokens are rejected with `Error::AlgMismatch`, verify them with a key
+/// as usual. The other verification functions always reject unsecured tokens,
+/// with or without this feature.
+#[derive(Debug, Default)]
+pub struct InsecureVerifier {
+    _private: (),
+}
+
+impl InsecureVerifier {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode an unsecured token WITHOUT any signature, then validate claims
+    /// according to `options`.
+    pub fn verify_with_options<ExtraClaims: DeserializeOwned>(
+        &self,
+        token: &str,
+        options: &VerifyOptions,
+    ) -> Result<Verified<ExtraClaims>> {
+        if token.split('.').nth(2) != Some("") {
+            return Err(Error::InvalidToken);
+        }
+        let claims = decode_without_verify::<ExtraClaims>(token)?;
+        if !claims.header().alg.eq_ignore_ascii_case("none") {
+            return Err(Error::AlgMismatch);
+        }
+        let matched_auds = options.validate(claims.claims())?;
+        if options.has_validators() {
+            options.run_validators(&decode_payload(token)?)?;
+        }
+        Ok(Verified::new(claims, matched_auds))
+    }
+}
+
+#[cfg(all(test, feature = "sign", feature = "ecdsa"))]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::{Map, Value};
+
+    use super::*;
+    use crate::{
+        ecdsa::{EcdsaAlgorithm, EcdsaPrivateKey},
+        sign, url_safe_trailing_bits, verify, HeaderAndClaims,
+    };
+
+    #[test]
+    fn insecure_verifier() -> Result<()> {
+        let unsecured = |claims: &str| {
+            format!(
+                "{}.{}.",
+                base64::encode_config(r#"{"alg":"none"}"#, url_safe_trailing_bits()),
+                base64::encode_config(claims, url_safe_trailing_bits()),
+            )
+        };
+        let token = unsecured(r#"{"sub":"dev"}"#);
+        let k = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+
+        assert!(matches!(
+            verify::<Map<String, Value>>(&token, &k),
+            Err(Error::UnsecuredToken)
+        ));
+
+        let v = InsecureVerifier::new();
+        let verified =
+            v.verify_with_options::<Map<String, Value>>(&token, &VerifyOptions::new())?;
+        assert_eq!(
+            verified.header_and_claims().claims().sub.as_deref(),
+            Some("dev")
+        );
+
+        // Claims are still validated.
+        let expired = unsecured(r#"{"exp":1}"#);
+        assert!(matches!(
+            v.verify_with_options::<Map<String, Value>>(&expired, &VerifyOptions::new()),
+            Err(Error::Expired)
+        ));
+
+        // Signed tokens are not accepted without their signature checked.
+        let signed = sign(
+            HeaderAndClaims::new_dynamic().set_exp_from_now(Duration::from_secs(60)),
+            &k,
+        )?;
+        assert!(v
+            .verify_with_options::<Map<String, Value>>(&signed, &VerifyOptions::new())
+            .is_err());
+        let stripped = format!("{}.", signed.rsplit_once('.').unwrap().0);
+        assert!(matches!(
+            v.verify_with_options::<Map<String, Value>>(&stripped, &VerifyOptions::new()),
+            Err(Error::AlgMismatch)
+        ));
+
+        Ok(())
+    }
+}
@@ -1,31 +1,56 @@
 //! JWK and JWK Set.
 //!
 //! Only public keys are really supported for now.
+// With every asymmetric family disabled no key can be loaded.
+#![cfg_attr(
+    not(any(feature = "ecdsa", feature = "eddsa", feature = "rsa")),
+    allow(dead_code, irrefutable_let_patterns)
+)]
 
 use std::collections::{BTreeMap, HashMap};
-
+#[cfg(any(feature = "ecdsa", feature = "eddsa", feature = "rsa"))]
+use std::convert::TryFrom;
+
+#[cfg(all(feature = "ecdsa", feature = "sign"))]
+use crate::ecdsa::EcdsaPrivateKey;
+#[cfg(feature = "ecdsa")]
+use crate::ecdsa::{EcdsaAlgorithm, EcdsaPublicKey};
+#[cfg(all(feature = "eddsa", feature = "sign"))]
+use crate::eddsa::Ed25519PrivateKey;
+#[cfg(feature = "eddsa")]
+use crate::eddsa::Ed25519PublicKey;
+#[cfg(any(feature = "rsa", feature = "sign"))]
+use crate::rsa::RsaAlgorithm;
+#[cfg(all(feature = "rsa", feature = "sign"))]
+use crate::rsa::RsaPrivateKey;
+#[cfg(feature = "rsa")]
+use crate::rsa::RsaPublicKey;
 use crate::{
-    ecdsa::{EcdsaAlgorithm, EcdsaPrivateKey, EcdsaPublicKey},
-    eddsa::{Ed25519PrivateKey, Ed25519PublicKey},
-    rsa::{RsaAlgorithm, RsaPrivateKey, RsaPublicKey},
-    some::SomePublicKey,
-    url_safe_trailing_bits, verify, verify_only, Error, Header, HeaderAndClaims, PublicKeyToJwk,
-    Result, SigningKey, SomePrivateKey, VerificationKey,
+    decode_header, decode_without_verify, some::SomePublicKey, url_safe_trailing_bits, verify,
+    verify_only, verify_with_options, Error, HeaderAndClaims, KeyPolicy, PublicKeyToJwk, Result,
+    VerificationKey, Verified, VerifyOptions,
 };
+#[cfg(feature = "sign")]
+use crate::{SigningKey, SomePrivateKey};
+#[cfg(all(feature = "rsa", feature = "sign"))]
 use openssl::{
     bn::BigNum,
-    hash::{hash, MessageDigest},
     pkey::PKey,
     rsa::{Rsa, RsaPrivateKeyBuilder},
 };
+use openssl::{
+    hash::{hash, MessageDigest},
+    stack::Stack,
+    x509::{store::X509StoreRef, X509StoreContext, X509},
+};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 
 // TODO: private key jwk.
 
 /// JWK Representation.
 #[non_exhaustive]
-#[derive(Debug, Deserialize, Serialize, Default)]
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub struct Jwk {
     pub kty: String,
     #[serde(rename = "use", skip_serializing_if = "Option::is_none")]
@@ -38,6 +63,12 @@ pub struct Jwk {
     pub crv: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub kid: Option<String>,
+    /// X.509 certificate SHA-256 thumbprint.
+    #[serde(rename = "x5t#S256", skip_serializing_if = "Option::is_none")]
+    pub x5t_s256: Option<String>,
+    /// X.509 certificate chain, standard base64 encoded DER, leaf first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub x5c: Vec<String>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub n: Option<String>,
@@ -65,8 +96,144 @@ pub struct Jwk {
     pub oth: Vec<Value>,
 }
 
+/// Options for `PublicKeyToJwk::public_key_to_jwk_with_options`.
+///
+/// The default options export exactly like `public_key_to_jwk`, i.e. with
+/// `use: "sig"`, no `key_ops`, and `alg` if the key type sets it.
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub struct JwkExportOptions {
+    use_: Option<String>,
+    key_ops: Vec<String>,
+    include_alg: bool,
+}
+
+impl Default for JwkExportOptions {
+    fn default() -> Self {
+        Self {
+            use_: Some("sig".into()),
+            key_ops: Vec::new(),
+            include_alg: true,
+        }
+    }
+}
+
+impl JwkExportOptions {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set `use`, or leave it out with `None`.
+    #[inline]
+    pub fn set_use(&mut self, use_: Option<String>) -> &mut Self {
+        self.use_ = use_;
+        self
+    }
+
+    /// Set `key_ops`, e.g. `["verify"]`. RFC 7517 recommends against using
+    /// both `use` and `key_ops`.
+    #[inline]
+    pub fn set_key_ops(&mut self, key_ops: Vec<String>) -> &mut Self {
+        self.key_ops = key_ops;
+        self
+    }
+
+    /// Whether to include `alg`. On by default.
+    #[inline]
+    pub fn set_include_alg(&mut self, include: bool) -> &mut Self {
+        self.include_alg = include;
+        self
+    }
+
+    pub(crate) fn apply(&self, jwk: &mut Jwk) {
+        jwk.use_ = self.use_.clone();
+        jwk.key_ops = self.key_ops.clone();
+        if !self.include_alg {
+            jwk.alg = None;
+        }
+    }
+}
+
 impl Jwk {
     pub fn to_verification_key(&self) -> Result<SomePublicKey> {
+        match &*self.kty {
+            #[cfg(feature = "rsa")]
+            "RSA" => RsaPublicKey::try_from(self).map(Into::into),
+            #[cfg(feature = "ecdsa")]
+            "EC" => EcdsaPublicKey::try_from(self).map(Into::into),
+            #[cfg(feature = "eddsa")]
+            "OKP" => Ed25519PublicKey::try_from(self).map(Into::into),
+            _ => Err(Error::UnsupportedOrInvalidKey),
+        }
+    }
+
+    /// Like `to_verification_key`, but fails with `Error::KeyPolicyViolation`
+    /// if the key is not allowed by `policy`.
+    pub fn to_verification_key_with_policy(&self, policy: &KeyPolicy) -> Result<SomePublicKey> {
+        let k = self.to_verification_key()?;
+        policy.check_key(&k)?;
+        Ok(k)
+    }
+
+    /// The JWS `alg` this key implies, derived from `alg`, `kty` and `crv`
+    /// without constructing the key, e.g. to pre-filter a large JWK set.
+    ///
+    /// EC keys imply the algorithm of their curve, e.g. `ES256` for `P-256`,
+    /// and `Ed25519` OKP keys imply `EdDSA`. RSA keys imply their `alg` if it
+    /// is recognized, otherwise `RS256`.
+    pub fn implied_alg(&self) -> Result<&'static str> {
+        match &*self.kty {
+            #[cfg(feature = "rsa")]
+            "RSA" => Ok(self
+                .alg
+                .as_deref()
+                .and_then(|alg| RsaAlgorithm::from_name(alg).ok())
+                .unwrap_or(RsaAlgorithm::RS256)
+                .name()),
+            #[cfg(feature = "ecdsa")]
+            "EC" => {
+                let crv = self.crv.as_deref().ok_or(Error::UnsupportedOrInvalidKey)?;
+                Ok(EcdsaAlgorithm::from_curve_name(crv)?.name())
+            }
+            #[cfg(feature = "eddsa")]
+            "OKP" => match self.crv.as_deref().map(canonical_okp_crv) {
+                Some("Ed25519") => Ok("EdDSA"),
+                _ => Err(Error::UnsupportedOrInvalidKey),
+            },
+            _ => Err(Error::UnsupportedOrInvalidKey),
+        }
+    }
+
+    /// Validate the `x5c` certificate chain against `trust_store`, and check
+    /// that the leaf certificate has the same key material as this JWK.
+    ///
+    /// Fails with `Error::InvalidCertificateChain` if `x5c` is empty or the
+    /// chain does not verify.
+    pub fn verify_x5c_chain(&self, trust_store: &X509StoreRef) -> Result<()> {
+        let mut certs = self
+            .x5c
+            .iter()
+            .map(|c| Ok(X509::from_der(&base64::decode(c)?)?))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter();
+        let leaf = certs.next().ok_or(Error::InvalidCertificateChain)?;
+        let mut chain = Stack::new()?;
+        for c in certs {
+            chain.push(c)?;
+        }
+
+        let mut ctx = X509StoreContext::new()?;
+        if !ctx.init(trust_store, &leaf, &chain, |c| c.verify_cert())? {
+            return Err(Error::InvalidCertificateChain);
+        }
+        if SomePublicKey::from_pkey(leaf.public_key()?)? != self.to_verification_key()? {
+            return Err(Error::InvalidCertificateChain);
+        }
+        Ok(())
+    }
+
+    fn check_verification_use(&self) -> Result<()> {
         // Check `use` and `key_ops`.
         if !matches!(self.use_.as_deref(), None | Some("sig")) {
             return Err(Error::UnsupportedOrInvalidKey);
@@ -74,68 +241,22 @@ impl Jwk {
         if !(self.key_ops.is_empty() || self.key_ops.iter().any(|ops| ops == "verify")) {
             return Err(Error::UnsupportedOrInvalidKey);
         }
-
-        // If let would be too long.
-        #[allow(clippy::single_match)]
-        match &*self.kty {
-            "RSA" => match (self.alg.as_deref(), &self.n, &self.e) {
-                (alg, Some(ref n), Some(ref e)) => {
-                    let n = base64::decode_config(n, url_safe_trailing_bits())?;
-                    let e = base64::decode_config(e, url_safe_trailing_bits())?;
-                    // If `alg` is specified, the key will only verify
-                    // signatures generated by ONLY this specific `alg`,
-                    // otherwise it will verify signatures generated by ANY RSA
-                    // algorithm.
-                    let alg = if let Some(alg) = alg {
-                        Some(RsaAlgorithm::from_name(alg)?)
-                    } else {
-                        None
-                    };
-                    return Ok(SomePublicKey::Rsa(RsaPublicKey::from_components(
-                        &n, &e, alg,
-                    )?));
-                }
-                _ => {}
-            },
-            "EC" => match (self.crv.as_deref(), &self.x, &self.y) {
-                // For EC keys `crv` is required.
-                (Some(crv), Some(ref x), Some(ref y)) => {
-                    let x = base64::decode_config(x, url_safe_trailing_bits())?;
-                    let y = base64::decode_config(y, url_safe_trailing_bits())?;
-                    let alg = EcdsaAlgorithm::from_curve_name(crv)?;
-                    return Ok(SomePublicKey::Ecdsa(EcdsaPublicKey::from_coordinates(
-                        &x, &y, alg,
-                    )?));
-                }
-                _ => {}
-            },
-            "OKP" => match (self.crv.as_deref(), &self.x) {
-                (Some(crv), Some(ref x)) => {
-                    let x = base64::decode_config(x, url_safe_trailing_bits())?;
-                    match crv {
-                        "Ed25519" => {
-                            return Ok(SomePublicKey::Ed25519(Ed25519PublicKey::from_bytes(&x)?));
-                        }
-                        _ => {}
-                    }
-                }
-                _ => {}
-            },
-            _ => {}
-        }
-
-        Err(Error::UnsupportedOrInvalidKey)
+        Ok(())
     }
 
     #[allow(clippy::many_single_char_names)]
+    #[cfg(feature = "sign")]
+    #[cfg_attr(not(feature = "rsa"), allow(unused_variables))]
     pub fn to_signing_key(&self, rsa_fallback_algorithm: RsaAlgorithm) -> Result<SomePrivateKey> {
         match &*self.kty {
+            #[cfg(feature = "rsa")]
             "RSA" => {
-                let alg = if let Some(ref alg) = self.alg {
-                    RsaAlgorithm::from_name(alg)?
-                } else {
-                    rsa_fallback_algorithm
-                };
+                // Unrecognized `alg` values are treated as if absent.
+                let alg = self
+                    .alg
+                    .as_deref()
+                    .and_then(|alg| RsaAlgorithm::from_name(alg).ok())
+                    .unwrap_or(rsa_fallback_algorithm);
                 match (self.d.as_deref(), self.n.as_deref(), self.e.as_deref()) {
                     (Some(d), Some(n), Some(e)) => {
                         fn decode(x: &str) -> Result<BigNum> {
@@ -176,6 +297,7 @@ impl Jwk {
                     _ => Err(Error::UnsupportedOrInvalidKey),
                 }
             }
+            #[cfg(feature = "ecdsa")]
             "EC" => {
                 match (
                     self.crv.as_deref(),
@@ -193,7 +315,11 @@ impl Jwk {
                     _ => Err(Error::UnsupportedOrInvalidKey),
                 }
             }
-            "OKP" => match (self.crv.as_deref(), self.d.as_deref()) {
+            #[cfg(feature = "eddsa")]
+            "OKP" => match (
+                self.crv.as_deref().map(canonical_okp_crv),
+                self.d.as_deref(),
+            ) {
                 (Some("Ed25519"), Some(d)) => {
                     let d = base64::decode_config(d, url_safe_trailing_bits())?;
                     Ed25519PrivateKey::from_bytes(&d).map(Into::into)
@@ -241,7 +367,10 @@ impl Jwk {
                 let mut v = BTreeMap::new();
                 v.insert(
                     "crv",
-                    self.crv.as_deref().ok_or(Error::UnsupportedOrInvalidKey)?,
+                    self.crv
+                        .as_deref()
+                        .map(canonical_okp_crv)
+                        .ok_or(Error::UnsupportedOrInvalidKey)?,
                 );
                 v.insert("kty", "OKP");
                 v.insert(
@@ -265,6 +394,109 @@ impl Jwk {
             url_safe_trailing_bits(),
         ))
     }
+
+    /// A canonical string of all members, e.g. to cache key objects derived
+    /// from this JWK by content rather than by `kid` alone, which may be
+    /// reused across rotations.
+    ///
+    /// Members are sorted and `=` padding is removed from the base64url key
+    /// parameters, so equal keys have equal cache keys however they were
+    /// serialized.
+    pub fn cache_key(&self) -> String {
+        const BASE64URL_MEMBERS: &[&str] = &["n", "e", "x", "y", "d", "p", "q", "dp", "dq", "qi"];
+
+        let members = match serde_json::to_value(self) {
+            Ok(Value::Object(m)) => m,
+            _ => unreachable!("a Jwk always serializes to an object"),
+        };
+        let sorted: BTreeMap<String, Value> = members
+            .into_iter()
+            .map(|(k, v)| match v {
+                Value::String(s) if BASE64URL_MEMBERS.contains(&&*k) => {
+                    let s = s.trim_end_matches('=').into();
+                    (k, Value::String(s))
+                }
+                v => (k, v),
+            })
+            .collect();
+        serde_json::to_string(&sorted).unwrap_or_default()
+    }
+}
+
+/// Some producers lowercase the OKP `crv`, e.g. `ed25519`.
+fn canonical_okp_crv(crv: &str) -> &str {
+    ["Ed25519", "Ed448", "X25519", "X448"]
+        .iter()
+        .find(|c| c.eq_ignore_ascii_case(crv))
+        .copied()
+        .unwrap_or(crv)
+}
+
+#[cfg(feature = "rsa")]
+impl TryFrom<&Jwk> for RsaPublicKey {
+    type Error = Error;
+
+    fn try_from(jwk: &Jwk) -> Result<Self> {
+        jwk.check_verification_use()?;
+        match (&*jwk.kty, jwk.alg.as_deref(), &jwk.n, &jwk.e) {
+            ("RSA", alg, Some(ref n), Some(ref e)) => {
+                let n = base64::decode_config(n, url_safe_trailing_bits())?;
+                let e = base64::decode_config(e, url_safe_trailing_bits())?;
+                // If a recognized `alg` is specified, the key will only verify
+                // signatures generated by ONLY this specific `alg`, otherwise
+                // it will verify signatures generated by ANY RSA algorithm.
+                let alg = alg.and_then(|alg| RsaAlgorithm::from_name(alg).ok());
+                RsaPublicKey::from_components(&n, &e, alg)
+            }
+            _ => Err(Error::UnsupportedOrInvalidKey),
+        }
+    }
+}
+
+#[cfg(feature = "ecdsa")]
+impl TryFrom<&Jwk> for EcdsaPublicKey {
+    type Error = Error;
+
+    fn try_from(jwk: &Jwk) -> Result<Self> {
+        jwk.check_verification_use()?;
+        match (&*jwk.kty, jwk.crv.as_deref(), &jwk.x, &jwk.y) {
+            // For EC keys `crv` is required.
+            ("EC", Some(crv), Some(ref x), Some(ref y)) => {
+                let x = base64::decode_config(x, url_safe_trailing_bits())?;
+                let y = base64::decode_config(y, url_safe_trailing_bits())?;
+                let alg = EcdsaAlgorithm::from_curve_name(crv)?;
+                // The curve determines the algorithm, e.g. `ES256K` for
+                // `secp256k1`, so another ECDSA `alg` can't be honored.
+                // Unrecognized values are ignored.
+                if jwk
+                    .alg
+                    .as_deref()
+                    .and_then(|a| EcdsaAlgorithm::from_name(a).ok())
+                    .map_or(false, |a| a != alg)
+                {
+                    return Err(Error::UnsupportedOrInvalidKey);
+                }
+                EcdsaPublicKey::from_coordinates(&x, &y, alg)
+            }
+            _ => Err(Error::UnsupportedOrInvalidKey),
+        }
+    }
+}
+
+#[cfg(feature = "eddsa")]
+impl TryFrom<&Jwk> for Ed25519PublicKey {
+    type Error = Error;
+
+    fn try_from(jwk: &Jwk) -> Result<Self> {
+        jwk.check_verification_use()?;
+        match (&*jwk.kty, jwk.crv.as_deref().map(canonical_okp_crv), &jwk.x) {
+            ("OKP", Some("Ed25519"), Some(ref x)) => {
+                let x = base64::decode_config(x, url_safe_trailing_bits())?;
+                Ed25519PublicKey::from_bytes(&x)
+            }
+            _ => Err(Error::UnsupportedOrInvalidKey),
+        }
+    }
 }
 
 /// JWK Set Representation.
@@ -275,37 +507,425 @@ pub struct JwkSet {
 
 impl JwkSet {
     pub fn verifier(&self) -> JwkSetVerifier {
-        let mut prepared = JwkSetVerifier {
-            keys: HashMap::new(),
-            require_kid: true,
-        };
-        for k in self.keys.iter() {
+        self.verifier_filtered(|_| true)
+    }
+
+    /// Like `verifier`, but only keys whose `x5c` certificate chain verifies
+    /// against `trust_store` are used, see `Jwk::verify_x5c_chain`.
+    pub fn verifier_with_x5c_chain(&self, trust_store: &X509StoreRef) -> JwkSetVerifier {
+        self.verifier_filtered(|k| k.verify_x5c_chain(trust_store).is_ok())
+    }
+
+    /// Like `verifier`, but only keys allowed by `policy` are used.
+    pub fn verifier_with_policy(&self, policy: &KeyPolicy) -> JwkSetVerifier {
+        self.verifier_filtered(|k| k.to_verification_key_with_policy(policy).is_ok())
+    }
+
+    /// Read every PEM public key file in directory `path`, e.g. a config
+    /// directory of `*.pub.pem` files. Files that don't look like PEM are
+    /// skipped. Keys get their JWK thumbprint as `kid`, like
+    /// `WithKid::new_with_thumbprint_id`.
+    ///
+    /// Fails if a PEM file can't be parsed as a supported public key.
+    pub fn from_pem_dir(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(path)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                paths.push(entry.path());
+            }
+        }
+        // Stable order regardless of the file system.
+        paths.sort();
+
+        let mut keys = Vec::new();
+        for p in paths {
+            let pem = std::fs::read(p)?;
+            if !pem.starts_with(b"-----BEGIN ") {
+                continue;
+            }
+            let k = SomePublicKey::from_pem(&pem)?;
+            keys.push(WithKid::new_with_thumbprint_id(k)?.public_key_to_jwk()?);
+        }
+        Ok(Self { keys })
+    }
+
+    fn verifier_filtered(&self, accept: impl Fn(&Jwk) -> bool) -> JwkSetVerifier {
+        let mut prepared = JwkSetVerifier::from_keys(HashMap::new());
+        for k in self.keys.iter().filter(|k| accept(k)) {
             if let Some(ref kid) = k.kid {
                 if let Ok(vk) = k.to_verification_key() {
                     prepared.keys.insert(kid.clone(), vk);
                 }
+            } else if let Some(ref x5t) = k.x5t_s256 {
+                if let Ok(vk) = k.to_verification_key() {
+                    prepared.x5t_s256_keys.insert(x5t.clone(), vk);
+                }
             }
         }
         prepared
     }
 }
 
+/// Health report of a JWK Set document, see `validate_jwks`.
+///
+/// Keys are referred to by their index in the `keys` array.
+#[non_exhaustive]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JwksReport {
+    pub num_keys: usize,
+    /// Keys with `use` absent or `sig`.
+    pub signing_keys: Vec<usize>,
+    /// Keys with `use` `enc`.
+    pub encryption_keys: Vec<usize>,
+    /// Signing keys that can't be parsed or used to verify signatures, with
+    /// the reason.
+    pub invalid_keys: Vec<(usize, String)>,
+    /// Kids used by more than one key.
+    pub duplicate_kids: Vec<String>,
+    /// RSA keys with a modulus shorter than 2048 bits.
+    pub weak_rsa_keys: Vec<usize>,
+    /// Keys without a `kid`.
+    pub missing_kids: Vec<usize>,
+}
+
+impl JwksReport {
+    /// No invalid or weak keys, duplicate or missing kids.
+    pub fn is_healthy(&self) -> bool {
+        self.invalid_keys.is_empty()
+            && self.duplicate_kids.is_empty()
+            && self.weak_rsa_keys.is_empty()
+            && self.missing_kids.is_empty()
+    }
+}
+
+/// Check a JWK Set document for common problems.
+///
+/// Returns an error only if `json` is not a JSON object with a `keys` array.
+/// Problems with individual keys are reported in the `JwksReport`.
+pub fn validate_jwks(json: &str) -> Result<JwksReport> {
+    #[derive(Deserialize)]
+    struct RawJwkSet {
+        keys: Vec<serde_json::Value>,
+    }
+
+    let raw: RawJwkSet = serde_json::from_str(json)?;
+    let mut report = JwksReport {
+        num_keys: raw.keys.len(),
+        ..JwksReport::default()
+    };
+    let mut kids = HashMap::<String, usize>::new();
+    for (i, k) in raw.keys.into_iter().enumerate() {
+        let jwk: Jwk = match serde_json::from_value(k) {
+            Ok(jwk) => jwk,
+            Err(e) => {
+                report.invalid_keys.push((i, e.to_string()));
+                continue;
+            }
+        };
+
+        match &jwk.kid {
+            Some(kid) => *kids.entry(kid.clone()).or_default() += 1,
+            None => report.missing_kids.push(i),
+        }
+
+        if jwk.use_.as_deref() == Some("enc") {
+            report.encryption_keys.push(i);
+            continue;
+        }
+        report.signing_keys.push(i);
+
+        if jwk.kty == "RSA" {
+            let n = jwk
+                .n
+                .as_ref()
+                .and_then(|n| base64::decode_config(n, url_safe_trailing_bits()).ok());
+            if let Some(n) = n {
+                let n = match n.iter().position(|b| *b != 0) {
+                    Some(p) => &n[p..],
+                    None => &[][..],
+                };
+                let bits = match n.first() {
+                    Some(b) => n.len() * 8 - b.leading_zeros() as usize,
+                    None => 0,
+                };
+                if bits < 2048 {
+                    report.weak_rsa_keys.push(i);
+                }
+            }
+        }
+
+        if let Err(e) = jwk.to_verification_key() {
+            report.invalid_keys.push((i, e.to_string()));
+        }
+    }
+    report.duplicate_kids = kids
+        .into_iter()
+        .filter(|(_, n)| *n > 1)
+        .map(|(kid, _)| kid)
+        .collect();
+    report.duplicate_kids.sort();
+    Ok(report)
+}
+
+/// Verify a token with keys from a JWK Set JSON document, then validate claims
+/// according to `options`.
+///
+/// Convenient for one-shot verification. The key set is parsed on every call,
+/// so use `JwkSet::verifier` to verify more than one token.
+pub fn verify_with_jwks_str<ExtraClaims: DeserializeOwned>(
+    token: &str,
+    jwks_json: &str,
+    options: &VerifyOptions,
+) -> Result<Verified<ExtraClaims>> {
+    serde_json::from_str::<JwkSet>(jwks_json)?
+        .verifier()
+        .verify_with_options(token, options)
+}
+
+/// Verify a token with keys from `get_keys`, e.g. an async closure that
+/// loads them from a custom store, then validate claims according to
+/// `options`.
+pub async fn verify_with_jwks_fn<ExtraClaims, F, Fut>(
+    token: &str,
+    get_keys: F,
+    options: &VerifyOptions,
+) -> Result<Verified<ExtraClaims>>
+where
+    ExtraClaims: DeserializeOwned,
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<std::sync::Arc<JwkSet>>>,
+{
+    verify_batch_with_jwks_fn(&[token], get_keys, options)
+        .await?
+        .pop()
+        .unwrap()
+}
+
+/// Like `verify_with_jwks_fn`, but for many tokens. `get_keys` is called at
+/// most once per batch, and its result is used for all `tokens`.
+///
+/// Fails if `get_keys` fails, otherwise returns the result of each token in
+/// order.
+pub async fn verify_batch_with_jwks_fn<ExtraClaims, F, Fut>(
+    tokens: &[&str],
+    mut get_keys: F,
+    options: &VerifyOptions,
+) -> Result<Vec<Result<Verified<ExtraClaims>>>>
+where
+    ExtraClaims: DeserializeOwned,
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<std::sync::Arc<JwkSet>>>,
+{
+    if tokens.is_empty() {
+        return Ok(Vec::new());
+    }
+    let verifier = get_keys().await?.verifier();
+    Ok(tokens
+        .iter()
+        .map(|token| verifier.verify_with_options(token, options))
+        .collect())
+}
+
+type VerifyFuture<'a> = std::pin::Pin<
+    Box<dyn std::future::Future<Output = Result<Verified<Map<String, Value>>>> + Send + 'a>,
+>;
+
+/// A source of JWK Sets, e.g. `RemoteJwksVerifier` fetching them from a URL, or
+/// a fixed `Arc<JwkSet>`.
+pub trait JwksProvider: Send + Sync {
+    /// Get the current JWK Set.
+    fn get_keys(
+        &self,
+    ) -> std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<std::sync::Arc<JwkSet>>> + Send + '_>,
+    >;
+
+    /// Verify token with the current keys, then validate claims according to
+    /// `options`.
+    ///
+    /// By default the keys from `get_keys` are converted for every token.
+    /// Providers with a prepared verifier, e.g. `RemoteJwksVerifier` or
+    /// `PreparedJwkSet`, use it instead, with its `require_kid` etc. settings.
+    fn verify_with_options<'a>(
+        &'a self,
+        token: &'a str,
+        options: &'a VerifyOptions,
+    ) -> VerifyFuture<'a> {
+        Box::pin(verify_with_jwks_fn(token, move || self.get_keys(), options))
+    }
+}
+
+impl JwksProvider for std::sync::Arc<JwkSet> {
+    fn get_keys(
+        &self,
+    ) -> std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<std::sync::Arc<JwkSet>>> + Send + '_>,
+    > {
+        let jwks = self.clone();
+        Box::pin(async move { Ok(jwks) })
+    }
+}
+
+/// A fixed JWK Set, converted once and then used for all tokens, unlike a plain
+/// `Arc<JwkSet>`.
+pub struct PreparedJwkSet {
+    jwks: std::sync::Arc<JwkSet>,
+    verifier: JwkSetVerifier,
+}
+
+impl PreparedJwkSet {
+    pub fn new(jwks: std::sync::Arc<JwkSet>) -> Self {
+        Self {
+            verifier: jwks.verifier(),
+            jwks,
+        }
+    }
+
+    /// The verifier used for all tokens, e.g. to `set_require_kid` or
+    /// `set_pinned_key`.
+    pub fn verifier_mut(&mut self) -> &mut JwkSetVerifier {
+        &mut self.verifier
+    }
+}
+
+impl JwksProvider for PreparedJwkSet {
+    fn get_keys(
+        &self,
+    ) -> std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<std::sync::Arc<JwkSet>>> + Send + '_>,
+    > {
+        self.jwks.get_keys()
+    }
+
+    fn verify_with_options<'a>(
+        &'a self,
+        token: &'a str,
+        options: &'a VerifyOptions,
+    ) -> VerifyFuture<'a> {
+        let verified = self.verifier.verify_with_options(token, options);
+        Box::pin(async move { verified })
+    }
+}
+
 /// Jwk set parsed and converted, ready to verify tokens.
+///
+/// It only ever uses the keys it was created with and never makes any network
+/// requests.
 pub struct JwkSetVerifier {
     keys: HashMap<String, SomePublicKey>,
+    // Keys without a `kid` but with a `x5t#S256`.
+    x5t_s256_keys: HashMap<String, SomePublicKey>,
     require_kid: bool,
+    require_kid_for: Vec<String>,
+    pinned_key: Option<SomePublicKey>,
+    x5t_s256_as_kid: bool,
+    uniform_failure: bool,
 }
 
 impl JwkSetVerifier {
+    /// From already resolved keys, indexed by `kid`.
+    pub fn from_keys(keys: HashMap<String, SomePublicKey>) -> Self {
+        Self {
+            keys,
+            x5t_s256_keys: HashMap::new(),
+            require_kid: true,
+            require_kid_for: Vec::new(),
+            pinned_key: None,
+            x5t_s256_as_kid: false,
+            uniform_failure: false,
+        }
+    }
+
+    /// From a bundle of concatenated PEM public keys, see
+    /// `SomePublicKey::from_pem_bundle`. Keys that fail to parse are skipped
+    /// and their errors returned.
+    ///
+    /// Keys are indexed by their JWK thumbprint, like
+    /// `WithKid::new_with_thumbprint_id`, and `require_kid` is off, so tokens
+    /// without a `kid` are verified with the only key of their `alg` in the
+    /// bundle.
+    pub fn from_pem_bundle(pem: &[u8]) -> (Self, Vec<Error>) {
+        let mut keys = HashMap::new();
+        let mut errors = Vec::new();
+        for k in SomePublicKey::from_pem_bundle(pem) {
+            match k.and_then(|k| {
+                let kid = k.public_key_to_jwk()?.get_thumbprint_sha256_base64()?;
+                Ok((kid, k))
+            }) {
+                Ok((kid, k)) => {
+                    keys.insert(kid, k);
+                }
+                Err(e) => errors.push(e),
+            }
+        }
+        let mut verifier = Self::from_keys(keys);
+        verifier.set_require_kid(false);
+        (verifier, errors)
+    }
+
     /// If called with `false`, subsequent `verify` and `verify_only` calls will
-    /// try all keys from the key set if a `kid` is not specified in the token.
+    /// use the only key of the token's `alg` from the key set if a `kid` is not
+    /// specified in the token. With more than one such key they fail with
+    /// `Error::AmbiguousKey`.
+    ///
+    /// Otherwise such tokens fail with `Error::NoKid`.
     pub fn set_require_kid(&mut self, required: bool) {
         self.require_kid = required;
     }
 
+    /// Require a `kid` for tokens with one of these `alg`s, e.g. `HS256`, even
+    /// if `require_kid` is off. Such tokens without a `kid` fail with
+    /// `Error::NoKid`.
+    ///
+    /// Verifying a HMAC token with the wrong key is much worse than with the
+    /// wrong public key, so this allows the no-`kid` fallback for asymmetric
+    /// algorithms only.
+    pub fn set_require_kid_for(&mut self, algs: Vec<String>) {
+        self.require_kid_for = algs;
+    }
+
+    /// If set, subsequent `verify` and `verify_only` calls will fail with
+    /// `Error::PinnedKeyMismatch` if the key selected from the key set does not
+    /// have the same key material as `pinned_key`, even if the signature could
+    /// be verified.
+    pub fn set_pinned_key(&mut self, pinned_key: Option<SomePublicKey>) {
+        self.pinned_key = pinned_key;
+    }
+
+    /// If called with `true`, keys without a `kid` can be found by their
+    /// `x5t#S256` thumbprint, i.e. a token `kid` is also matched against the
+    /// `x5t#S256` of such keys.
+    pub fn set_x5t_s256_as_kid(&mut self, enabled: bool) {
+        self.x5t_s256_as_kid = enabled;
+    }
+
+    /// If called with `true`, a token with an unknown `kid` is still verified
+    /// with some key of the set before failing with `Error::NoKey`.
+    ///
+    /// Without it an unknown `kid` is rejected much faster than a bad
+    /// signature, so by timing responses an attacker can tell which `kid`s
+    /// exist, e.g. to find keys that are about to be rotated in. With it both
+    /// take about one signature verification. Differences between key types,
+    /// e.g. RSA and EC, are not hidden.
+    ///
+    /// Off by default, as it wastes a verification per rejected token.
+    pub fn set_uniform_failure(&mut self, enabled: bool) {
+        self.uniform_failure = enabled;
+    }
+
+    fn check_pinned_key(&self, k: &SomePublicKey) -> Result<()> {
+        match self.pinned_key {
+            Some(ref pinned) if pinned != k => Err(Error::PinnedKeyMismatch),
+            _ => Ok(()),
+        }
+    }
+
     pub fn find(&self, kid: &str) -> Option<&SomePublicKey> {
         if let Some(vk) = self.keys.get(kid) {
             Some(vk)
+        } else if self.x5t_s256_as_kid {
+            self.x5t_s256_keys.get(kid)
         } else {
             None
         }
@@ -313,12 +933,12 @@ impl JwkSetVerifier {
 
     /// Decode and verify token with keys from this JWK set.
     ///
-    /// The `alg`, `exp` and `nbf` fields are automatically checked.
+    /// The `alg`, `exp`, `nbf` and `iat` fields are automatically checked.
     pub fn verify<ExtraClaims: DeserializeOwned>(
         &self,
         token: &str,
     ) -> Result<HeaderAndClaims<ExtraClaims>> {
-        self.find_and_verify(token, verify)
+        self.find_and_verify(token, None, |token, k| verify(token, k))
     }
 
     /// Decode and verify token with keys from this JWK set. Won't check `exp` and `nbf`.
@@ -326,76 +946,176 @@ impl JwkSetVerifier {
         &self,
         token: &str,
     ) -> Result<HeaderAndClaims<ExtraClaims>> {
-        self.find_and_verify(token, verify_only)
+        self.find_and_verify(token, None, |token, k| verify_only(token, k))
+    }
+
+    /// Decode and verify token with keys from this JWK set, then validate
+    /// claims according to `options`.
+    pub fn verify_with_options<ExtraClaims: DeserializeOwned>(
+        &self,
+        token: &str,
+        options: &VerifyOptions,
+    ) -> Result<Verified<ExtraClaims>> {
+        self.find_and_verify(token, Some(options), |token, k| {
+            if let Some(policy) = options.key_policy() {
+                policy.check_key(k)?;
+            }
+            verify_with_options(token, k, options)
+        })
     }
 
     /// Find and verify token with keys from this JWK set.
     ///
     /// restrict_kid is true will only match keys with the same `kid`.
-    fn find_and_verify<ExtraClaims: DeserializeOwned>(
+    ///
+    /// Keys are matched against the `alg` as normalized by `options`.
+    fn find_and_verify<T>(
         &self,
         token: &str,
-        verifier: fn(&str, &dyn VerificationKey) -> Result<HeaderAndClaims<ExtraClaims>>,
-    ) -> Result<HeaderAndClaims<ExtraClaims>> {
-        let mut parts = token.split('.');
-
-        let mut header = parts.next().ok_or(Error::InvalidToken)?.as_bytes();
-
-        let header_r = base64::read::DecoderReader::new(&mut header, url_safe_trailing_bits());
-        let header: Header = serde_json::from_reader(header_r)?;
+        options: Option<&VerifyOptions>,
+        verifier: impl Fn(&str, &SomePublicKey) -> Result<T>,
+    ) -> Result<T> {
+        let header = decode_header(token)?;
+        let alg = match options {
+            Some(options) => options.normalize_alg(&header.alg),
+            None => std::borrow::Cow::Borrowed(&*header.alg),
+        };
 
         if let Some(kid) = header.kid {
-            let k = self.find(&kid).ok_or(Error::NoKey)?;
-            verifier(token, k)
-        } else if !self.require_kid {
-            if let Some(res) = self
-                .keys
-                .iter()
-                .map(|(_, key)| verifier(token, key))
-                .find_map(|res| res.ok())
-            {
-                Ok(res)
-            } else {
-                Err(Error::NoKey)
+            let k = match self.find(&kid) {
+                Some(k) => k,
+                None => {
+                    if self.uniform_failure {
+                        // The result is irrelevant, the token may even verify
+                        // with a key of another `kid`.
+                        if let Some(k) =
+                            self.keys.values().chain(self.x5t_s256_keys.values()).next()
+                        {
+                            let _ = verifier(token, k);
+                        }
+                    }
+                    return Err(Error::NoKey);
+                }
+            };
+            self.check_pinned_key(k)?;
+            // Never let e.g. an `HS256` token be checked against an RSA key.
+            if !k.accepts_alg(&alg) {
+                return Err(Error::AlgMismatch);
             }
+            verifier(token, k)
         } else {
-            Err(Error::NoKey)
+            let require_kid = self.require_kid
+                || self
+                    .require_kid_for
+                    .iter()
+                    .any(|a| a.eq_ignore_ascii_case(&alg));
+            let candidates = self
+                .keys
+                .values()
+                .chain(self.x5t_s256_keys.values())
+                .filter(|key| key.accepts_alg(&alg))
+                .filter(|key| self.check_pinned_key(key).is_ok());
+            verifier(token, key_without_kid(require_kid, candidates)?)
         }
     }
 }
 
-/// A key associated with a key id (`kid`).
+/// The key for a token without a `kid`, see `JwkSetVerifier::set_require_kid`.
 ///
-/// When the key is used for signing, `kid` is automatically set.
-#[derive(Debug)]
-pub struct WithKid<S> {
-    kid: String,
-    inner: S,
+/// Fails with `Error::NoKid` if `require_kid`, otherwise returns the only one
+/// of `candidates`.
+pub(crate) fn key_without_kid<K>(
+    require_kid: bool,
+    mut candidates: impl Iterator<Item = K>,
+) -> Result<K> {
+    if require_kid {
+        return Err(Error::NoKid);
+    }
+    match (candidates.next(), candidates.next()) {
+        (Some(k), None) => Ok(k),
+        (Some(_), Some(_)) => Err(Error::AmbiguousKey),
+        (None, _) => Err(Error::NoKey),
+    }
 }
 
-impl<S> WithKid<S> {
-    pub fn new(kid: String, inner: S) -> Self {
-        Self { kid, inner }
-    }
+/// A verifier that is guaranteed to never fetch keys.
+///
+/// Unlike the verifiers built on a `JwksProvider`, it has no fetch path at
+/// all: it only ever uses the keys it was created with.
+pub struct OfflineVerifier {
+    verifier: JwkSetVerifier,
+}
 
-    /// Use key thumbprint as key id.
-    pub fn new_with_thumbprint_id(inner: S) -> Result<Self>
-    where
-        S: PublicKeyToJwk,
-    {
-        Ok(Self {
-            kid: inner.public_key_to_jwk()?.get_thumbprint_sha256_base64()?,
-            inner,
-        })
+impl OfflineVerifier {
+    /// With the keys of `jwks`, see `JwkSet::verifier`.
+    pub fn new(jwks: &JwkSet) -> Self {
+        Self {
+            verifier: jwks.verifier(),
+        }
     }
 
-    pub fn kid(&self) -> &str {
-        &self.kid
+    /// From already resolved keys, indexed by `kid`.
+    pub fn from_keys(keys: HashMap<String, SomePublicKey>) -> Self {
+        Self {
+            verifier: JwkSetVerifier::from_keys(keys),
+        }
     }
 
-    pub fn set_kid(&mut self, kid: impl Into<String>) {
-        self.kid = kid.into();
-    }
+    pub fn verify<ExtraClaims: DeserializeOwned>(
+        &self,
+        token: &str,
+    ) -> Result<HeaderAndClaims<ExtraClaims>> {
+        self.verifier.verify(token)
+    }
+
+    pub fn verify_only<ExtraClaims: DeserializeOwned>(
+        &self,
+        token: &str,
+    ) -> Result<HeaderAndClaims<ExtraClaims>> {
+        self.verifier.verify_only(token)
+    }
+
+    pub fn verify_with_options<ExtraClaims: DeserializeOwned>(
+        &self,
+        token: &str,
+        options: &VerifyOptions,
+    ) -> Result<Verified<ExtraClaims>> {
+        self.verifier.verify_with_options(token, options)
+    }
+}
+
+/// A key associated with a key id (`kid`).
+///
+/// When the key is used for signing, `kid` is automatically set.
+#[derive(Debug)]
+pub struct WithKid<S> {
+    kid: String,
+    inner: S,
+}
+
+impl<S> WithKid<S> {
+    pub fn new(kid: String, inner: S) -> Self {
+        Self { kid, inner }
+    }
+
+    /// Use key thumbprint as key id.
+    pub fn new_with_thumbprint_id(inner: S) -> Result<Self>
+    where
+        S: PublicKeyToJwk,
+    {
+        Ok(Self {
+            kid: inner.public_key_to_jwk()?.get_thumbprint_sha256_base64()?,
+            inner,
+        })
+    }
+
+    pub fn kid(&self) -> &str {
+        &self.kid
+    }
+
+    pub fn set_kid(&mut self, kid: impl Into<String>) {
+        self.kid = kid.into();
+    }
 
     pub fn as_inner(&self) -> &S {
         &self.inner
@@ -410,6 +1130,7 @@ impl<S> WithKid<S> {
     }
 }
 
+#[cfg(feature = "sign")]
 impl<S: SigningKey> SigningKey for WithKid<S> {
     fn kid(&self) -> Option<&str> {
         Some(&self.kid)
@@ -438,117 +1159,706 @@ impl<K: PublicKeyToJwk> PublicKeyToJwk for WithKid<K> {
     }
 }
 
+/// Whether a remote JWK set was served from cache or freshly fetched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeySource {
+    Cache,
+    Fetched,
+}
+
+/// Upper bound of the cache lifetime a JWKS server can ask for.
+#[cfg(feature = "remote-jwks")]
+const MAX_CACHE_LIFETIME: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+
+/// How long a response may be cached according to its `Cache-Control:
+/// max-age`, or else its `Expires` header (relative to its `Date`, or `now`),
+/// at most `MAX_CACHE_LIFETIME`.
+///
+/// `None` if both are absent or invalid, or if the response is already
+/// expired, e.g. `max-age=0`: caching it for no time at all would refetch the
+/// key set on every verification.
+#[cfg(feature = "remote-jwks")]
+fn cache_lifetime(
+    headers: &reqwest::header::HeaderMap,
+    now: std::time::SystemTime,
+) -> Option<std::time::Duration> {
+    use reqwest::header::{CACHE_CONTROL, DATE, EXPIRES};
+
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+
+    let max_age = header(CACHE_CONTROL).and_then(|cc| {
+        cc.split(',').find_map(|directive| {
+            let (name, value) = directive.trim().split_once('=')?;
+            if name.eq_ignore_ascii_case("max-age") {
+                value.trim_matches('"').parse().ok()
+            } else {
+                None
+            }
+        })
+    });
+    let lifetime = match max_age {
+        Some(max_age) => std::time::Duration::from_secs(max_age),
+        None => {
+            let expires = httpdate::parse_http_date(header(EXPIRES)?).ok()?;
+            let date = header(DATE)
+                .and_then(|d| httpdate::parse_http_date(d).ok())
+                .unwrap_or(now);
+            // Already expired.
+            expires.duration_since(date).unwrap_or_default()
+        }
+    };
+    if lifetime == std::time::Duration::from_secs(0) {
+        None
+    } else {
+        Some(lifetime.min(MAX_CACHE_LIFETIME))
+    }
+}
+
 #[cfg(feature = "remote-jwks")]
+#[derive(Clone)]
 struct JWKSCache {
-    jwks: JwkSetVerifier,
+    jwks: std::sync::Arc<JwkSet>,
+    verifier: std::sync::Arc<JwkSetVerifier>,
+    fetched_at_system: std::time::SystemTime,
+    /// Last fetch attempt, successful or not.
+    attempted_at: std::time::Instant,
     valid_until: std::time::Instant,
 }
 
 /// A JWK Set served from a remote url. Automatically fetched and cached.
+///
+/// The cached key set is shared behind an `Arc`: a refresh replaces it, so
+/// readers never have to clone it. Refreshes are single-flight and fetch
+/// outside of the cache lock, so verifications with a usable cached key set
+/// never wait for one.
+///
+/// The verifier is `Send + Sync` and all verification methods take `&self`, so
+/// configure it once and share it with `Arc<RemoteJwksVerifier>`, e.g. as
+/// web framework state. See `examples/axum_extractor.rs`.
 #[cfg(feature = "remote-jwks")]
 pub struct RemoteJwksVerifier {
     url: String,
     client: reqwest::Client,
     cache_duration: std::time::Duration,
-    cache: tokio::sync::RwLock<Option<JWKSCache>>,
+    unknown_kid_refetch_interval: std::time::Duration,
+    timeout: Option<std::time::Duration>,
+    cache: tokio::sync::RwLock<Option<std::sync::Arc<JWKSCache>>>,
+    /// Held while fetching, so that concurrent misses share one fetch.
+    fetch_lock: tokio::sync::Mutex<()>,
     require_kid: bool,
+    require_kid_for: Vec<String>,
+    pinned_key: Option<SomePublicKey>,
+    x5t_s256_as_kid: bool,
+    uniform_failure: bool,
 }
 
 #[cfg(feature = "remote-jwks")]
 impl RemoteJwksVerifier {
+    /// The url must use the `https` scheme, otherwise `Error::InsecureUrl` is
+    /// returned.
+    ///
+    /// The key set is cached as long as the response `Cache-Control: max-age`,
+    /// or else `Expires`, header allows, or for `cache_duration` without them
+    /// or if they are already expired (e.g. `max-age=0`).
     pub fn new(
         url: String,
         client: Option<reqwest::Client>,
         cache_duration: std::time::Duration,
+    ) -> Result<Self> {
+        if !reqwest::Url::parse(&url).map_or(false, |u| u.scheme() == "https") {
+            return Err(Error::InsecureUrl);
+        }
+        Ok(Self::new_allow_insecure_http(url, client, cache_duration))
+    }
+
+    /// Like `new`, but also accepts plain `http` urls.
+    ///
+    /// Keys fetched over `http` can be tampered with, so this should only be
+    /// used for local testing.
+    pub fn new_allow_insecure_http(
+        url: String,
+        client: Option<reqwest::Client>,
+        cache_duration: std::time::Duration,
     ) -> Self {
         Self {
             url,
             client: client.unwrap_or_default(),
             cache_duration,
+            unknown_kid_refetch_interval: std::time::Duration::from_secs(30),
+            timeout: None,
             cache: tokio::sync::RwLock::new(None),
+            fetch_lock: tokio::sync::Mutex::new(()),
+            require_kid: true,
+            require_kid_for: Vec::new(),
+            pinned_key: None,
+            x5t_s256_as_kid: false,
+            uniform_failure: false,
+        }
+    }
+
+    /// Configure a verifier for `url` with a builder. The defaults match
+    /// `new` with a cache duration of 5 minutes.
+    pub fn builder(url: impl Into<String>) -> RemoteJwksVerifierBuilder {
+        RemoteJwksVerifierBuilder {
+            url: url.into(),
+            client: None,
+            cache_duration: std::time::Duration::from_secs(300),
+            unknown_kid_refetch_interval: std::time::Duration::from_secs(30),
+            timeout: None,
+            allow_insecure_http: false,
             require_kid: true,
+            require_kid_for: Vec::new(),
+            pinned_key: None,
+            x5t_s256_as_kid: false,
+            uniform_failure: false,
+        }
+    }
+
+    /// Discover the JWKS url of an OpenID Connect `issuer` from
+    /// `{issuer}/.well-known/openid-configuration`.
+    ///
+    /// The discovery document `issuer` must equal `issuer`, otherwise
+    /// `Error::InvalidIssuer` is returned. Both the discovery url and the JWKS
+    /// url must use the `https` scheme.
+    pub async fn from_issuer(
+        issuer: &str,
+        client: Option<reqwest::Client>,
+        cache_duration: std::time::Duration,
+    ) -> Result<Self> {
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            issuer.trim_end_matches('/')
+        );
+        Self::discover(&url, Some(issuer), client, cache_duration, false).await
+    }
+
+    /// Like `from_issuer`, but for issuers that host their discovery document
+    /// at a non-standard url.
+    ///
+    /// The discovery document `issuer` is only checked if `expected_issuer` is
+    /// given.
+    pub async fn from_discovery_url(
+        discovery_url: &str,
+        expected_issuer: Option<&str>,
+        client: Option<reqwest::Client>,
+        cache_duration: std::time::Duration,
+    ) -> Result<Self> {
+        Self::discover(
+            discovery_url,
+            expected_issuer,
+            client,
+            cache_duration,
+            false,
+        )
+        .await
+    }
+
+    /// Like `from_issuer`, but also accepts plain `http` urls, see
+    /// `new_allow_insecure_http`.
+    pub async fn from_issuer_allow_insecure_http(
+        issuer: &str,
+        client: Option<reqwest::Client>,
+        cache_duration: std::time::Duration,
+    ) -> Result<Self> {
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            issuer.trim_end_matches('/')
+        );
+        Self::discover(&url, Some(issuer), client, cache_duration, true).await
+    }
+
+    /// Like `from_discovery_url`, but also accepts plain `http` urls, see
+    /// `new_allow_insecure_http`.
+    pub async fn from_discovery_url_allow_insecure_http(
+        discovery_url: &str,
+        expected_issuer: Option<&str>,
+        client: Option<reqwest::Client>,
+        cache_duration: std::time::Duration,
+    ) -> Result<Self> {
+        Self::discover(discovery_url, expected_issuer, client, cache_duration, true).await
+    }
+
+    async fn discover(
+        discovery_url: &str,
+        expected_issuer: Option<&str>,
+        client: Option<reqwest::Client>,
+        cache_duration: std::time::Duration,
+        allow_insecure_http: bool,
+    ) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct Discovery {
+            issuer: String,
+            jwks_uri: String,
+        }
+
+        if !allow_insecure_http
+            && !reqwest::Url::parse(discovery_url).map_or(false, |u| u.scheme() == "https")
+        {
+            return Err(Error::InsecureUrl);
+        }
+        let client = client.unwrap_or_default();
+        let discovery: Discovery = client
+            .get(discovery_url)
+            .header("accept", "application/json")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if expected_issuer.map_or(false, |iss| iss != discovery.issuer) {
+            return Err(Error::InvalidIssuer);
+        }
+        if allow_insecure_http {
+            Ok(Self::new_allow_insecure_http(
+                discovery.jwks_uri,
+                Some(client),
+                cache_duration,
+            ))
+        } else {
+            Self::new(discovery.jwks_uri, Some(client), cache_duration)
         }
     }
 
     /// If called with `false`, subsequent `verify` and `verify_only` calls will
-    /// try all keys from the key set if a `kid` is not specified in the token.
+    /// use the only key of the token's `alg` from the key set if a `kid` is not
+    /// specified in the token. With more than one such key they fail with
+    /// `Error::AmbiguousKey`.
+    ///
+    /// Otherwise such tokens fail with `Error::NoKid`.
     pub fn set_require_kid(&mut self, required: bool) {
         self.require_kid = required;
-        if let Some(ref mut v) = self.cache.get_mut() {
-            v.jwks.require_kid = required;
+        self.rebuild_cached_verifier();
+    }
+
+    /// See `JwkSetVerifier::set_require_kid_for`.
+    pub fn set_require_kid_for(&mut self, algs: Vec<String>) {
+        self.require_kid_for = algs;
+        self.rebuild_cached_verifier();
+    }
+
+    /// When a token's key is not found in the cached key set, the key set is
+    /// refetched to pick up newly rotated keys, but at most once per
+    /// `interval`. Within that window unknown `kid`s fail with
+    /// `Error::NoKey` without a fetch, so spamming them can't flood the JWKS
+    /// endpoint. Failed fetches count too.
+    ///
+    /// The refetch is single-flight: concurrent verifications that miss the
+    /// same stale key set wait for one fetch and then retry their lookup once
+    /// against the new key set. Verifications with keys of the cached set
+    /// don't wait for it.
+    ///
+    /// Defaults to 30 seconds.
+    pub fn set_unknown_kid_refetch_interval(&mut self, interval: std::time::Duration) {
+        self.unknown_kid_refetch_interval = interval;
+    }
+
+    /// Abort JWKS fetches that take longer than `timeout` in total (connect
+    /// and read). A timed out fetch serves the cached key set even if it has
+    /// expired, or fails with `Error::FetchTimeout` if nothing is cached. The
+    /// stale key set is then used without another fetch for the unknown `kid`
+    /// refetch interval.
+    ///
+    /// No timeout by default, other than any configured on the client.
+    pub fn set_timeout(&mut self, timeout: Option<std::time::Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// See `JwkSetVerifier::set_pinned_key`.
+    pub fn set_pinned_key(&mut self, pinned_key: Option<SomePublicKey>) {
+        self.pinned_key = pinned_key;
+        self.rebuild_cached_verifier();
+    }
+
+    /// See `JwkSetVerifier::set_x5t_s256_as_kid`.
+    pub fn set_x5t_s256_as_kid(&mut self, enabled: bool) {
+        self.x5t_s256_as_kid = enabled;
+        self.rebuild_cached_verifier();
+    }
+
+    /// See `JwkSetVerifier::set_uniform_failure`.
+    pub fn set_uniform_failure(&mut self, enabled: bool) {
+        self.uniform_failure = enabled;
+        self.rebuild_cached_verifier();
+    }
+
+    fn build_verifier(&self, jwks: &JwkSet) -> JwkSetVerifier {
+        let mut v = jwks.verifier();
+        v.require_kid = self.require_kid;
+        v.require_kid_for = self.require_kid_for.clone();
+        v.pinned_key = self.pinned_key.clone();
+        v.x5t_s256_as_kid = self.x5t_s256_as_kid;
+        v.uniform_failure = self.uniform_failure;
+        v
+    }
+
+    fn rebuild_cached_verifier(&mut self) {
+        if let Some(c) = self.cache.get_mut().take() {
+            *self.cache.get_mut() = Some(std::sync::Arc::new(JWKSCache {
+                verifier: std::sync::Arc::new(self.build_verifier(&c.jwks)),
+                ..(*c).clone()
+            }));
         }
     }
 
-    async fn get_verifier(&self) -> Result<tokio::sync::RwLockReadGuard<'_, JwkSetVerifier>> {
-        let cache = self.cache.read().await;
-        // Cache still valid.
-        if let Some(c) = &*cache {
-            if c.valid_until
+    /// Get the cached key set, fetching it if the cache is empty, expired, or
+    /// was last attempted to be fetched no later than `stale_if_attempted_at`.
+    ///
+    /// Only one fetch runs at a time, and the cache lock is not held during
+    /// it, so readers of a usable cache never wait for it.
+    async fn get_cache(
+        &self,
+        stale_if_attempted_at: Option<std::time::Instant>,
+    ) -> Result<(std::sync::Arc<JWKSCache>, KeySource)> {
+        let usable = |c: &JWKSCache| {
+            c.valid_until
                 .checked_duration_since(std::time::Instant::now())
                 .is_some()
-            {
-                return Ok(tokio::sync::RwLockReadGuard::map(cache, |c| {
-                    &c.as_ref().unwrap().jwks
-                }));
+                && stale_if_attempted_at.map_or(true, |t| c.attempted_at > t)
+        };
+
+        // Cache still valid.
+        if let Some(c) = &*self.cache.read().await {
+            if usable(c) {
+                return Ok((c.clone(), KeySource::Cache));
             }
         }
-        drop(cache);
 
-        let mut cache = self.cache.write().await;
-        if let Some(c) = &*cache {
-            if c.valid_until
-                .checked_duration_since(std::time::Instant::now())
-                .is_some()
-            {
-                return Ok(tokio::sync::RwLockReadGuard::map(cache.downgrade(), |c| {
-                    &c.as_ref().unwrap().jwks
-                }));
+        let _fetching = self.fetch_lock.lock().await;
+        // Fetched by someone else while waiting.
+        let cached = self.cache.read().await.clone();
+        if let Some(c) = &cached {
+            if usable(c) {
+                return Ok((c.clone(), KeySource::Cache));
             }
         }
-        let response = self
-            .client
-            .get(&self.url)
-            .header("accept", "application/json")
-            .send()
-            .await?;
-        let jwks: JwkSet = response.json().await?;
+        let fetched = async {
+            let mut request = self
+                .client
+                .get(&self.url)
+                .header("accept", "application/json");
+            if let Some(timeout) = self.timeout {
+                request = request.timeout(timeout);
+            }
+            let response = request.send().await?;
+            let lifetime = cache_lifetime(response.headers(), std::time::SystemTime::now());
+            Ok::<_, reqwest::Error>((response.json::<JwkSet>().await?, lifetime))
+        }
+        .await;
+        let now = std::time::Instant::now();
+        let (jwks, lifetime) = match (fetched, cached) {
+            (Ok(fetched), _) => fetched,
+            (Err(e), None) if e.is_timeout() => return Err(Error::FetchTimeout),
+            (Err(e), None) => return Err(e.into()),
+            (Err(e), Some(c)) => {
+                // Failed attempts count for `unknown_kid_refetch_interval` too,
+                // so an unreachable server isn't hammered.
+                let mut c = JWKSCache {
+                    attempted_at: now,
+                    ..(*c).clone()
+                };
+                // Keep serving the stale key set for a while instead of
+                // waiting for the timeout again on every verification.
+                if e.is_timeout() {
+                    let retry_at = now + self.unknown_kid_refetch_interval;
+                    c.valid_until = c.valid_until.max(retry_at);
+                }
+                let c = std::sync::Arc::new(c);
+                *self.cache.write().await = Some(c.clone());
+                return if e.is_timeout() {
+                    Ok((c, KeySource::Cache))
+                } else {
+                    Err(e.into())
+                };
+            }
+        };
 
-        *cache = Some(JWKSCache {
-            jwks: {
-                let mut v = jwks.verifier();
-                v.require_kid = self.require_kid;
-                v
-            },
-            valid_until: std::time::Instant::now() + self.cache_duration,
+        let c = std::sync::Arc::new(JWKSCache {
+            verifier: std::sync::Arc::new(self.build_verifier(&jwks)),
+            jwks: std::sync::Arc::new(jwks),
+            fetched_at_system: std::time::SystemTime::now(),
+            attempted_at: now,
+            valid_until: now
+                .checked_add(lifetime.unwrap_or(self.cache_duration))
+                .unwrap_or_else(|| now + MAX_CACHE_LIFETIME),
         });
+        *self.cache.write().await = Some(c.clone());
+
+        Ok((c, KeySource::Fetched))
+    }
+
+    /// Run `f` with the cached verifier. If the key is not found, refetch the
+    /// key set (at most once per `unknown_kid_refetch_interval`) and retry.
+    async fn with_verifier<T>(
+        &self,
+        f: impl Fn(&JwkSetVerifier) -> Result<T>,
+    ) -> Result<(T, KeySource)> {
+        let (c, key_source) = self.get_cache(None).await?;
+        match f(&c.verifier) {
+            Err(Error::NoKey)
+                if key_source == KeySource::Cache
+                    && c.attempted_at.elapsed() >= self.unknown_kid_refetch_interval =>
+            {
+                let (c, key_source) = self.get_cache(Some(c.attempted_at)).await?;
+                Ok((f(&c.verifier)?, key_source))
+            }
+            res => Ok((res?, key_source)),
+        }
+    }
+
+    /// Get the current JWK Set, fetching it if the cache is empty or expired.
+    pub async fn jwk_set(&self) -> Result<std::sync::Arc<JwkSet>> {
+        Ok(self.get_cache(None).await?.0.jwks.clone())
+    }
+
+    /// When the cached JWK Set was last successfully fetched, or `None` if it
+    /// has not been fetched yet.
+    ///
+    /// The cached set is kept until a fetch succeeds, so this can be used to
+    /// alert on stale keys, e.g. when the issuer is unreachable.
+    pub async fn last_fetch_time(&self) -> Option<std::time::SystemTime> {
+        self.cache
+            .read()
+            .await
+            .as_ref()
+            .map(|c| c.fetched_at_system)
+    }
 
-        Ok(tokio::sync::RwLockReadGuard::map(cache.downgrade(), |c| {
-            &c.as_ref().unwrap().jwks
-        }))
+    /// Time left until the cached JWK Set expires and is refetched, e.g. to
+    /// align other cache layers with the issuer's caching headers.
+    ///
+    /// `None` if it has not been fetched yet, zero if it already expired.
+    pub async fn cache_ttl_remaining(&self) -> Option<std::time::Duration> {
+        self.cache.read().await.as_ref().map(|c| {
+            c.valid_until
+                .saturating_duration_since(std::time::Instant::now())
+        })
     }
 
     pub async fn verify<E: DeserializeOwned>(&self, token: &str) -> Result<HeaderAndClaims<E>> {
-        let v = self.get_verifier().await?;
-        v.verify(token)
+        Ok(self.with_verifier(|v| v.verify(token)).await?.0)
     }
 
     pub async fn verify_only<E: DeserializeOwned>(
         &self,
         token: &str,
     ) -> Result<HeaderAndClaims<E>> {
-        let v = self.get_verifier().await?;
-        v.verify_only(token)
+        Ok(self.with_verifier(|v| v.verify_only(token)).await?.0)
+    }
+
+    pub async fn verify_with_options<E: DeserializeOwned>(
+        &self,
+        token: &str,
+        options: &VerifyOptions,
+    ) -> Result<Verified<E>> {
+        let (verified, key_source) = self
+            .with_verifier(|v| v.verify_with_options(token, options))
+            .await?;
+        Ok(verified.with_key_source(key_source))
+    }
+}
+
+/// Builder for `RemoteJwksVerifier`, see `RemoteJwksVerifier::builder`.
+#[cfg(feature = "remote-jwks")]
+#[derive(Clone)]
+pub struct RemoteJwksVerifierBuilder {
+    url: String,
+    client: Option<reqwest::Client>,
+    cache_duration: std::time::Duration,
+    unknown_kid_refetch_interval: std::time::Duration,
+    timeout: Option<std::time::Duration>,
+    allow_insecure_http: bool,
+    require_kid: bool,
+    require_kid_for: Vec<String>,
+    pinned_key: Option<SomePublicKey>,
+    x5t_s256_as_kid: bool,
+    uniform_failure: bool,
+}
+
+#[cfg(feature = "remote-jwks")]
+impl RemoteJwksVerifierBuilder {
+    /// Use `client` instead of a default `reqwest::Client`.
+    pub fn client(&mut self, client: reqwest::Client) -> &mut Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Defaults to 5 minutes. Only used if the response has no valid
+    /// `Cache-Control: max-age` or `Expires` header.
+    pub fn cache_duration(&mut self, duration: std::time::Duration) -> &mut Self {
+        self.cache_duration = duration;
+        self
+    }
+
+    /// See `RemoteJwksVerifier::set_unknown_kid_refetch_interval`.
+    pub fn unknown_kid_refetch_interval(&mut self, interval: std::time::Duration) -> &mut Self {
+        self.unknown_kid_refetch_interval = interval;
+        self
+    }
+
+    /// See `RemoteJwksVerifier::set_timeout`.
+    pub fn timeout(&mut self, timeout: std::time::Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Also accept plain `http` urls, see
+    /// `RemoteJwksVerifier::new_allow_insecure_http`.
+    pub fn allow_insecure_http(&mut self, allow: bool) -> &mut Self {
+        self.allow_insecure_http = allow;
+        self
+    }
+
+    /// See `RemoteJwksVerifier::set_require_kid`.
+    pub fn require_kid(&mut self, required: bool) -> &mut Self {
+        self.require_kid = required;
+        self
+    }
+
+    /// See `JwkSetVerifier::set_require_kid_for`.
+    pub fn require_kid_for(&mut self, algs: Vec<String>) -> &mut Self {
+        self.require_kid_for = algs;
+        self
+    }
+
+    /// See `JwkSetVerifier::set_pinned_key`.
+    pub fn pinned_key(&mut self, pinned_key: SomePublicKey) -> &mut Self {
+        self.pinned_key = Some(pinned_key);
+        self
+    }
+
+    /// See `JwkSetVerifier::set_x5t_s256_as_kid`.
+    pub fn x5t_s256_as_kid(&mut self, enabled: bool) -> &mut Self {
+        self.x5t_s256_as_kid = enabled;
+        self
+    }
+
+    /// See `JwkSetVerifier::set_uniform_failure`.
+    pub fn uniform_failure(&mut self, enabled: bool) -> &mut Self {
+        self.uniform_failure = enabled;
+        self
+    }
+
+    /// Fails with `Error::InsecureUrl` if the url is not `https` (or `http`
+    /// with `allow_insecure_http`), and with `Error::InvalidConfig` if the
+    /// unknown `kid` refetch interval is zero, as that would let unknown
+    /// `kid`s trigger a fetch on every verification.
+    pub fn build(&self) -> Result<RemoteJwksVerifier> {
+        let scheme_ok = reqwest::Url::parse(&self.url).map_or(false, |u| {
+            u.scheme() == "https" || (self.allow_insecure_http && u.scheme() == "http")
+        });
+        if !scheme_ok {
+            return Err(Error::InsecureUrl);
+        }
+        if self.unknown_kid_refetch_interval.is_zero() {
+            return Err(Error::InvalidConfig(
+                "unknown kid refetch interval must not be zero".into(),
+            ));
+        }
+        let mut v = RemoteJwksVerifier::new_allow_insecure_http(
+            self.url.clone(),
+            self.client.clone(),
+            self.cache_duration,
+        );
+        v.unknown_kid_refetch_interval = self.unknown_kid_refetch_interval;
+        v.timeout = self.timeout;
+        v.require_kid = self.require_kid;
+        v.require_kid_for = self.require_kid_for.clone();
+        v.pinned_key = self.pinned_key.clone();
+        v.x5t_s256_as_kid = self.x5t_s256_as_kid;
+        v.uniform_failure = self.uniform_failure;
+        Ok(v)
+    }
+}
+
+#[cfg(feature = "remote-jwks")]
+impl JwksProvider for RemoteJwksVerifier {
+    fn get_keys(
+        &self,
+    ) -> std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<std::sync::Arc<JwkSet>>> + Send + '_>,
+    > {
+        Box::pin(self.jwk_set())
+    }
+
+    fn verify_with_options<'a>(
+        &'a self,
+        token: &'a str,
+        options: &'a VerifyOptions,
+    ) -> VerifyFuture<'a> {
+        Box::pin(RemoteJwksVerifier::verify_with_options(
+            self, token, options,
+        ))
+    }
+}
+
+/// Verify tokens from several issuers, each with its own JWK set, e.g. while
+/// migrating from an old issuer to a new one.
+///
+/// Tokens are routed by their `iss` claim to the `JwksProvider` of that
+/// issuer. Tokens without an `iss`, or from an unknown issuer, fail with
+/// `Error::InvalidIssuer`.
+#[derive(Default)]
+pub struct CompositeVerifier {
+    issuers: HashMap<String, Box<dyn JwksProvider>>,
+}
+
+impl CompositeVerifier {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Verify tokens from `iss` with keys from `provider`, e.g. a
+    /// `RemoteJwksVerifier` or a `PreparedJwkSet`.
+    pub fn add_issuer(
+        &mut self,
+        iss: impl Into<String>,
+        provider: impl JwksProvider + 'static,
+    ) -> &mut Self {
+        self.issuers.insert(iss.into(), Box::new(provider));
+        self
+    }
+
+    /// Decode and verify token with the keys of its issuer, then validate
+    /// claims according to `options`.
+    pub async fn verify_with_options<E: DeserializeOwned>(
+        &self,
+        token: &str,
+        options: &VerifyOptions,
+    ) -> Result<Verified<E>> {
+        // The claims are verified by the issuer's keys below, so routing on the
+        // unverified `iss` is fine.
+        let iss = decode_without_verify::<Map<String, Value>>(token)?
+            .claims
+            .iss
+            .ok_or(Error::InvalidIssuer)?;
+        let provider = self.issuers.get(&iss).ok_or(Error::InvalidIssuer)?;
+        provider
+            .verify_with_options(token, options)
+            .await?
+            .deserialize_extra()
+    }
+
+    /// Decode and verify token with the keys of its issuer.
+    ///
+    /// The `alg`, `exp`, `nbf` and `iat` fields are automatically checked.
+    pub async fn verify<E: DeserializeOwned>(&self, token: &str) -> Result<HeaderAndClaims<E>> {
+        self.verify_with_options(token, &VerifyOptions::default())
+            .await
+            .map(Verified::into_header_and_claims)
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "sign", feature = "ecdsa", feature = "eddsa"))]
 mod tests {
     use crate::{
         ecdsa::{EcdsaAlgorithm, EcdsaPrivateKey},
         eddsa::Ed25519PrivateKey,
-        rsa::RsaPrivateKey,
-        sign,
+        sign, PrivateKeyToJwk,
     };
+    use openssl::pkey::PKey;
+
+    use serde_json::{Map, Value};
 
     use super::*;
 
@@ -572,34 +1882,884 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "rsa")]
     #[test]
-    fn test_thumbprint() -> Result<()> {
-        RsaPrivateKey::generate(2048, RsaAlgorithm::RS256)?
-            .public_key_to_jwk()?
-            .get_thumbprint_sha256_base64()?;
-        EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?
-            .public_key_to_jwk()?
-            .get_thumbprint_sha256_base64()?;
-        Ed25519PrivateKey::generate()?
-            .public_key_to_jwk()?
-            .get_thumbprint_sha256_base64()?;
+    fn test_try_from_jwk() -> Result<()> {
+        let ec = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES384)?.public_key_to_jwk()?;
+        let rsa = RsaPrivateKey::generate(2048, RsaAlgorithm::RS256)?.public_key_to_jwk()?;
+        let ed = Ed25519PrivateKey::generate()?.public_key_to_jwk()?;
+
+        let ec_pk = EcdsaPublicKey::try_from(&ec)?;
+        assert_eq!(ec_pk.coordinates()?.0.len(), 48);
+        RsaPublicKey::try_from(&rsa)?;
+        Ed25519PublicKey::try_from(&ed)?;
+
+        assert!(EcdsaPublicKey::try_from(&rsa).is_err());
+        assert!(EcdsaPublicKey::try_from(&ed).is_err());
+        assert!(RsaPublicKey::try_from(&ec).is_err());
+        assert!(RsaPublicKey::try_from(&ed).is_err());
+        assert!(Ed25519PublicKey::try_from(&ec).is_err());
+        assert!(Ed25519PublicKey::try_from(&rsa).is_err());
+
         Ok(())
     }
 
-    #[derive(Serialize, Deserialize)]
-    struct MyClaim {
-        foo: String,
+    #[cfg(feature = "rsa")]
+    #[test]
+    fn test_jwk_unknown_alg() -> Result<()> {
+        let mut ec = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?.public_key_to_jwk()?;
+        ec.alg = Some("ES256+vendor".into());
+        assert!(matches!(ec.to_verification_key()?, SomePublicKey::Ecdsa(_)));
+
+        let k = RsaPrivateKey::generate(2048, RsaAlgorithm::PS256)?;
+        let mut rsa = k.public_key_to_jwk()?;
+        rsa.alg = Some("x-vendor-RS256".into());
+        let pk = RsaPublicKey::try_from(&rsa)?;
+        assert_eq!(pk.algorithm, None);
+        let sig = k.sign(b"...")?;
+        pk.verify(b"...", &sig, "PS256")?;
+
+        let mut rsa = k.private_key_to_jwk()?;
+        rsa.alg = Some("x-vendor-RS256".into());
+        assert_eq!(rsa.to_signing_key(RsaAlgorithm::RS384)?.alg(), "RS384");
+
+        Ok(())
     }
 
     #[test]
-    fn test_jwks_verify() -> Result<()> {
-        let k = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES512)?;
-        let kk = WithKid::new("my key".into(), k.clone());
-        let k_jwk = kk.public_key_to_jwk()?;
-        let jwks = JwkSet { keys: vec![k_jwk] };
+    fn test_jwks_pinned_key() -> Result<()> {
+        let k = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let k1 = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let jwks = JwkSet {
+            keys: vec![
+                WithKid::new("k".into(), k.clone()).public_key_to_jwk()?,
+                WithKid::new("k1".into(), k1.clone()).public_key_to_jwk()?,
+            ],
+        };
         let mut verifier = jwks.verifier();
+        let token = sign(
+            &mut HeaderAndClaims::new_dynamic(),
+            &WithKid::new("k".into(), k),
+        )?;
+        let token1 = sign(
+            &mut HeaderAndClaims::new_dynamic(),
+            &WithKid::new("k1".into(), k1),
+        )?;
+
+        let pinned = verifier.find("k").cloned();
+        assert!(pinned.is_some());
+        verifier.set_pinned_key(pinned);
+        verifier.verify::<Map<String, Value>>(&token)?;
+        assert!(matches!(
+            verifier.verify::<Map<String, Value>>(&token1),
+            Err(Error::PinnedKeyMismatch)
+        ));
+
+        verifier.set_pinned_key(None);
+        verifier.verify::<Map<String, Value>>(&token1)?;
 
-        // jwt with kid
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwks_x5t_s256_as_kid() -> Result<()> {
+        let k = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let jwks: JwkSet = serde_json::from_value(serde_json::json!({
+            "keys": [{
+                "kty": "EC",
+                "crv": "P-256",
+                "x": k.public_key_to_jwk()?.x,
+                "y": k.public_key_to_jwk()?.y,
+                "x5t#S256": "cert-thumbprint",
+            }]
+        }))?;
+        assert_eq!(jwks.keys[0].x5t_s256.as_deref(), Some("cert-thumbprint"));
+        assert!(serde_json::to_string(&jwks)?.contains(r#""x5t#S256":"cert-thumbprint""#));
+
+        let token = sign(
+            HeaderAndClaims::new_dynamic().set_kid("cert-thumbprint"),
+            &k,
+        )?;
+        let mut verifier = jwks.verifier();
+        assert!(matches!(
+            verifier.verify::<Map<String, Value>>(&token),
+            Err(Error::NoKey)
+        ));
+        verifier.set_x5t_s256_as_kid(true);
+        verifier.verify::<Map<String, Value>>(&token)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwks_uniform_failure() -> Result<()> {
+        let k = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let jwks = JwkSet {
+            keys: vec![WithKid::new("k".into(), k.clone()).public_key_to_jwk()?],
+        };
+        let mut verifier = jwks.verifier();
+        verifier.set_uniform_failure(true);
+
+        verifier.verify::<Map<String, Value>>(&sign(
+            &mut HeaderAndClaims::new_dynamic(),
+            &WithKid::new("k".into(), k.clone()),
+        )?)?;
+
+        // Signed by the key that is verified against, but the `kid` is unknown.
+        let unknown = sign(
+            &mut HeaderAndClaims::new_dynamic(),
+            &WithKid::new("unknown".into(), k),
+        )?;
+        assert!(matches!(
+            verifier.verify::<Map<String, Value>>(&unknown),
+            Err(Error::NoKey)
+        ));
+        assert!(matches!(
+            verifier.verify_with_options::<Map<String, Value>>(&unknown, &VerifyOptions::new()),
+            Err(Error::NoKey)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwks_require_kid_for() -> Result<()> {
+        let k = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let jwks = JwkSet {
+            keys: vec![WithKid::new("k".into(), k.clone()).public_key_to_jwk()?],
+        };
+        let mut verifier = jwks.verifier();
+        verifier.set_require_kid(false);
+        verifier.set_require_kid_for(vec!["HS256".into(), "HS384".into(), "HS512".into()]);
+
+        verifier.verify::<Map<String, Value>>(&sign(&mut HeaderAndClaims::new_dynamic(), &k)?)?;
+
+        let hs256 = format!(
+            "{}.{}.{}",
+            base64::encode_config(r#"{"alg":"HS256"}"#, url_safe_trailing_bits()),
+            base64::encode_config("{}", url_safe_trailing_bits()),
+            base64::encode_config([0u8; 32], url_safe_trailing_bits()),
+        );
+        assert!(matches!(
+            verifier.verify::<Map<String, Value>>(&hs256),
+            Err(Error::NoKid)
+        ));
+
+        Ok(())
+    }
+
+    #[cfg(all(feature = "rsa", feature = "hmac"))]
+    #[test]
+    fn test_jwks_rejects_hmac_with_public_key() -> Result<()> {
+        use crate::{
+            hmac::{HmacAlgorithm, HmacKey},
+            rsa::RsaPrivateKey,
+        };
+
+        let k = RsaPrivateKey::generate(2048, RsaAlgorithm::RS256)?;
+        let pem = k.public_key_to_pem()?;
+        let jwks = JwkSet {
+            keys: vec![WithKid::new("k".into(), k.clone()).public_key_to_jwk()?],
+        };
+        let mut verifier = jwks.verifier();
+
+        // HS256 with the public key PEM as the secret.
+        let attacker = HmacKey::from_bytes(pem.as_bytes(), HmacAlgorithm::HS256);
+        let with_kid = sign(
+            &mut HeaderAndClaims::new_dynamic(),
+            &WithKid::new("k".into(), attacker.clone()),
+        )?;
+        assert!(matches!(
+            verifier.verify::<Map<String, Value>>(&with_kid),
+            Err(Error::AlgMismatch)
+        ));
+
+        verifier.set_require_kid(false);
+        let without_kid = sign(&mut HeaderAndClaims::new_dynamic(), &attacker)?;
+        assert!(matches!(
+            verifier.verify::<Map<String, Value>>(&without_kid),
+            Err(Error::NoKey)
+        ));
+
+        verifier.verify::<Map<String, Value>>(&sign(&mut HeaderAndClaims::new_dynamic(), &k)?)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwk_set_from_pem_dir() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("jwtk-pem-dir-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+
+        let k1 = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let k2 = Ed25519PrivateKey::generate()?;
+        std::fs::write(dir.join("a.pub.pem"), k1.public_key_to_pem()?)?;
+        std::fs::write(dir.join("b.pub.pem"), k2.public_key_to_pem()?)?;
+        std::fs::write(dir.join("README"), "not a key")?;
+
+        let jwks = JwkSet::from_pem_dir(&dir);
+        std::fs::remove_dir_all(&dir)?;
+        let jwks = jwks?;
+
+        assert_eq!(jwks.keys.len(), 2);
+        let verifier = jwks.verifier();
+        let token = sign(
+            &mut HeaderAndClaims::new_dynamic(),
+            &WithKid::new_with_thumbprint_id(k1)?,
+        )?;
+        verifier.verify::<Map<String, Value>>(&token)?;
+        let token = sign(
+            &mut HeaderAndClaims::new_dynamic(),
+            &WithKid::new_with_thumbprint_id(k2)?,
+        )?;
+        verifier.verify::<Map<String, Value>>(&token)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwks_ambiguous_key() -> Result<()> {
+        let k1 = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let k2 = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let ed = Ed25519PrivateKey::generate()?;
+        let token = sign(&mut HeaderAndClaims::new_dynamic(), &k1)?;
+
+        let one = JwkSet {
+            keys: vec![
+                WithKid::new("k1".into(), k1.clone()).public_key_to_jwk()?,
+                WithKid::new("ed".into(), ed).public_key_to_jwk()?,
+            ],
+        };
+        let mut verifier = one.verifier();
+        assert!(matches!(
+            verifier.verify::<Map<String, Value>>(&token),
+            Err(Error::NoKid)
+        ));
+        // Only one key is for `ES256`.
+        verifier.set_require_kid(false);
+        verifier.verify::<Map<String, Value>>(&token)?;
+
+        let two = JwkSet {
+            keys: vec![
+                WithKid::new("k1".into(), k1).public_key_to_jwk()?,
+                WithKid::new("k2".into(), k2).public_key_to_jwk()?,
+            ],
+        };
+        let mut verifier = two.verifier();
+        assert!(matches!(
+            verifier.verify::<Map<String, Value>>(&token),
+            Err(Error::NoKid)
+        ));
+        verifier.set_require_kid(false);
+        assert!(matches!(
+            verifier.verify::<Map<String, Value>>(&token),
+            Err(Error::AmbiguousKey)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_url_kid() -> Result<()> {
+        let kid = "https://example.com/key/1?v=2&x=%2F#frag";
+        let k = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let jwks = JwkSet {
+            keys: vec![WithKid::new(kid.into(), k.clone()).public_key_to_jwk()?],
+        };
+        let json = serde_json::to_string(&jwks)?;
+        let jwks: JwkSet = serde_json::from_str(&json)?;
+        assert_eq!(jwks.keys[0].kid.as_deref(), Some(kid));
+        // JSON escaped slashes are the same kid.
+        let escaped: JwkSet = serde_json::from_str(&json.replace('/', "\\/"))?;
+        assert_eq!(escaped.keys[0].kid.as_deref(), Some(kid));
+
+        let verifier = jwks.verifier();
+        let token = sign(
+            &mut HeaderAndClaims::new_dynamic(),
+            &WithKid::new(kid.into(), k.clone()),
+        )?;
+        verifier.verify::<Map<String, Value>>(&token)?;
+
+        // No normalization, e.g. of a trailing slash or case.
+        for other in [
+            "https://example.com/key/1?v=2&x=%2F#frag/",
+            "HTTPS://example.com/key/1?v=2&x=%2F#frag",
+        ] {
+            let token = sign(
+                &mut HeaderAndClaims::new_dynamic(),
+                &WithKid::new(other.into(), k.clone()),
+            )?;
+            assert!(matches!(
+                verifier.verify::<Map<String, Value>>(&token),
+                Err(Error::NoKey)
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwk_cache_key() -> Result<()> {
+        let k = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let jwk = WithKid::new("k".into(), k).public_key_to_jwk()?;
+
+        // Different member order and padded coordinates.
+        let json = format!(
+            r#"{{"y":"{}=","x":"{}=","use":"sig","kid":"k","crv":"P-256","alg":"ES256","kty":"EC"}}"#,
+            jwk.y.as_deref().unwrap(),
+            jwk.x.as_deref().unwrap(),
+        );
+        let reserialized: Jwk = serde_json::from_str(&json)?;
+        assert_eq!(jwk.cache_key(), reserialized.cache_key());
+
+        let other = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let other = WithKid::new("k".into(), other).public_key_to_jwk()?;
+        assert_ne!(jwk.cache_key(), other.cache_key());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apple_style_jwks() -> Result<()> {
+        let k1 = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let k2 = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let member = |kid: &str, k: &EcdsaPrivateKey| -> Result<String> {
+            let jwk = k.public_key_to_jwk()?;
+            // Member order as served by appleid.apple.com/auth/keys.
+            Ok(format!(
+                r#"{{"kty":"EC","kid":"{}","use":"sig","alg":"ES256","crv":"P-256","x":"{}","y":"{}"}}"#,
+                kid,
+                jwk.x.unwrap(),
+                jwk.y.unwrap(),
+            ))
+        };
+        let jwks: JwkSet = serde_json::from_str(&format!(
+            r#"{{"keys":[{},{}]}}"#,
+            member("W6WcOKB", &k1)?,
+            member("fh6Bs8C", &k2)?,
+        ))?;
+        let verifier = jwks.verifier();
+
+        let mut token = HeaderAndClaims::new_dynamic();
+        token
+            .set_iss("https://appleid.apple.com")
+            .add_aud("com.example.app")
+            .set_sub("001234.abcdef.1234")
+            .set_iat_now()
+            .set_exp_from_now(std::time::Duration::from_secs(600))
+            .insert("c_hash", "pGlVOJ3YxYjH9R-n9bNYeQ")
+            .insert("email", "abc@privaterelay.appleid.com")
+            // Apple sends these booleans as strings.
+            .insert("email_verified", "true")
+            .insert("is_private_email", "true")
+            .insert("auth_time", 1_700_000_000)
+            .insert("nonce_supported", true);
+        token.header_mut().typ = None;
+        let token = sign(&mut token, &WithKid::new("fh6Bs8C".into(), k2))?;
+
+        let mut options = VerifyOptions::new();
+        options
+            .add_iss("https://appleid.apple.com")
+            .add_aud("com.example.app");
+        let verified = verifier.verify_with_options::<Map<String, Value>>(&token, &options)?;
+        assert_eq!(
+            verified.header_and_claims().claims().extra["email_verified"],
+            "true"
+        );
+
+        let mut options = VerifyOptions::new();
+        options
+            .add_iss("https://appleid.apple.com")
+            .add_aud("com.example.other");
+        assert!(verifier
+            .verify_with_options::<Map<String, Value>>(&token, &options)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwks_lenient_alg_case() -> Result<()> {
+        let k = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let jwks = JwkSet {
+            keys: vec![WithKid::new("k".into(), k.clone()).public_key_to_jwk()?],
+        };
+        let verifier = jwks.verifier();
+
+        let header =
+            base64::encode_config(r#"{"alg":"es256","kid":"k"}"#, url_safe_trailing_bits());
+        let payload = base64::encode_config("{}", url_safe_trailing_bits());
+        let input = format!("{}.{}", header, payload);
+        let sig = base64::encode_config(k.sign(input.as_bytes())?, url_safe_trailing_bits());
+        let token = format!("{}.{}", input, sig);
+
+        assert!(verifier
+            .verify_with_options::<Map<String, Value>>(&token, &VerifyOptions::new())
+            .is_err());
+        let mut lenient = VerifyOptions::new();
+        lenient.set_lenient_alg_case(true);
+        verifier.verify_with_options::<Map<String, Value>>(&token, &lenient)?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "brainpool")]
+    #[test]
+    fn test_jwks_brainpool() -> Result<()> {
+        let k = WithKid::new(
+            "k".into(),
+            EcdsaPrivateKey::generate(EcdsaAlgorithm::BP256R1)?,
+        );
+        let jwks = JwkSet {
+            keys: vec![k.public_key_to_jwk()?],
+        };
+        let token = sign(&mut HeaderAndClaims::new_dynamic(), &k)?;
+        jwks.verifier().verify::<Map<String, Value>>(&token)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_okp_crv_case_insensitive() -> Result<()> {
+        let k = Ed25519PrivateKey::generate()?;
+        let jwk = k.public_key_to_jwk()?;
+        let lower: Jwk = serde_json::from_value(serde_json::json!({
+            "kty": "OKP",
+            "crv": "ed25519",
+            "x": jwk.x,
+        }))?;
+
+        let pk = lower.to_verification_key()?;
+        assert_eq!(pk.public_key_to_jwk()?.crv.as_deref(), Some("Ed25519"));
+        assert_eq!(lower.get_thumbprint_sha256()?, jwk.get_thumbprint_sha256()?);
+        let token = sign(&mut HeaderAndClaims::new_dynamic(), &k)?;
+        verify::<Map<String, Value>>(&token, &pk)?;
+
+        let private = Jwk {
+            crv: Some("ED25519".into()),
+            ..k.private_key_to_jwk()?
+        };
+        private.to_signing_key(RsaAlgorithm::RS256)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_composite_verifier() -> Result<()> {
+        let old = WithKid::new(
+            "k".into(),
+            EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?,
+        );
+        let new = WithKid::new(
+            "k".into(),
+            EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?,
+        );
+        let jwks = |k: &WithKid<EcdsaPrivateKey>| -> Result<std::sync::Arc<JwkSet>> {
+            Ok(std::sync::Arc::new(JwkSet {
+                keys: vec![k.public_key_to_jwk()?],
+            }))
+        };
+        let mut verifier = CompositeVerifier::new();
+        verifier
+            .add_issuer("old", jwks(&old)?)
+            .add_issuer("new", PreparedJwkSet::new(jwks(&new)?));
+
+        let old_token = sign(HeaderAndClaims::new_dynamic().set_iss("old"), &old)?;
+        let new_token = sign(HeaderAndClaims::new_dynamic().set_iss("new"), &new)?;
+        verifier.verify::<Map<String, Value>>(&old_token).await?;
+        verifier.verify::<Map<String, Value>>(&new_token).await?;
+
+        // Signed by the other issuer's key.
+        let forged = sign(HeaderAndClaims::new_dynamic().set_iss("new"), &old)?;
+        assert!(matches!(
+            verifier.verify::<Map<String, Value>>(&forged).await,
+            Err(Error::VerificationError)
+        ));
+        for iss in [Some("other"), None] {
+            let mut claims = HeaderAndClaims::new_dynamic();
+            if let Some(iss) = iss {
+                claims.set_iss(iss);
+            }
+            let token = sign(&mut claims, &old)?;
+            assert!(matches!(
+                verifier.verify::<Map<String, Value>>(&token).await,
+                Err(Error::InvalidIssuer)
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "remote-jwks")]
+    #[tokio::test]
+    async fn test_composite_verifier_provider_settings() -> Result<()> {
+        let k = WithKid::new(
+            "k".into(),
+            EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?,
+        );
+        let other = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let jwks = JwkSet {
+            keys: vec![k.public_key_to_jwk()?],
+        };
+        let jwks_json = serde_json::to_string(&jwks)?;
+        let (url, _) = serve_json(move || jwks_json.clone());
+
+        let mut prepared = PreparedJwkSet::new(std::sync::Arc::new(jwks));
+        prepared.verifier_mut().set_require_kid(false);
+        let mut verifier = CompositeVerifier::new();
+        verifier
+            .add_issuer("prepared", prepared)
+            .add_issuer(
+                "remote",
+                RemoteJwksVerifier::builder(url.clone())
+                    .allow_insecure_http(true)
+                    .require_kid(false)
+                    .build()?,
+            )
+            .add_issuer(
+                "pinned",
+                RemoteJwksVerifier::builder(url)
+                    .allow_insecure_http(true)
+                    .pinned_key(other.public_key_to_jwk()?.to_verification_key()?)
+                    .build()?,
+            );
+
+        // Without a `kid`, only verified because the providers don't require
+        // one.
+        for iss in ["prepared", "remote"] {
+            let token = sign(HeaderAndClaims::new_dynamic().set_iss(iss), k.as_inner())?;
+            verifier.verify::<Map<String, Value>>(&token).await?;
+        }
+
+        let token = sign(HeaderAndClaims::new_dynamic().set_iss("pinned"), &k)?;
+        assert!(matches!(
+            verifier.verify::<Map<String, Value>>(&token).await,
+            Err(Error::PinnedKeyMismatch)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pem_bundle() -> Result<()> {
+        let ec = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES384)?;
+        let ed = Ed25519PrivateKey::generate()?;
+        let other = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES384)?;
+        let bundle = format!(
+            "{}\n{}-----BEGIN PUBLIC KEY-----\nbm90IGEga2V5\n-----END PUBLIC KEY-----\n",
+            ec.public_key_to_pem()?,
+            ed.public_key_to_pem()?,
+        );
+
+        let keys = SomePublicKey::from_pem_bundle(bundle.as_bytes());
+        assert_eq!(keys.len(), 3);
+        assert!(matches!(keys[0], Ok(SomePublicKey::Ecdsa(_))));
+        assert!(matches!(keys[1], Ok(SomePublicKey::Ed25519(_))));
+        assert!(keys[2].is_err());
+
+        let (verifier, errors) = JwkSetVerifier::from_pem_bundle(bundle.as_bytes());
+        assert_eq!(errors.len(), 1);
+        verifier.verify::<Map<String, Value>>(&sign(&mut HeaderAndClaims::new_dynamic(), &ec)?)?;
+        verifier.verify::<Map<String, Value>>(&sign(&mut HeaderAndClaims::new_dynamic(), &ed)?)?;
+        let with_kid = WithKid::new_with_thumbprint_id(ed)?;
+        verifier
+            .verify::<Map<String, Value>>(&sign(&mut HeaderAndClaims::new_dynamic(), &with_kid)?)?;
+        assert!(matches!(
+            verifier
+                .verify::<Map<String, Value>>(&sign(&mut HeaderAndClaims::new_dynamic(), &other)?),
+            Err(Error::VerificationError)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_with_jwks_str() -> Result<()> {
+        let k = WithKid::new(
+            "k".into(),
+            EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?,
+        );
+        let jwks = format!(
+            r#"{{"keys":[{}]}}"#,
+            serde_json::to_string(&k.public_key_to_jwk()?)?
+        );
+        let token = sign(HeaderAndClaims::new_dynamic().set_iss("me"), &k)?;
+
+        let mut options = VerifyOptions::new();
+        options.add_iss("me");
+        verify_with_jwks_str::<Map<String, Value>>(&token, &jwks, &options)?;
+
+        options.set_issuers(vec!["you".into()]);
+        assert!(matches!(
+            verify_with_jwks_str::<Map<String, Value>>(&token, &jwks, &options),
+            Err(Error::InvalidIssuer)
+        ));
+        let mut policy = KeyPolicy::new();
+        policy.set_allowed_algs(vec!["ES384".into()]);
+        assert!(matches!(
+            verify_with_jwks_str::<Map<String, Value>>(
+                &token,
+                &jwks,
+                VerifyOptions::new().set_key_policy(policy)
+            ),
+            Err(Error::KeyPolicyViolation(_))
+        ));
+        assert!(matches!(
+            verify_with_jwks_str::<Map<String, Value>>(&token, "{}", &options),
+            Err(Error::SerdeJson(_))
+        ));
+
+        Ok(())
+    }
+
+    fn make_cert(
+        subject: &str,
+        key: &EcdsaPrivateKey,
+        issuer: Option<(&X509, &EcdsaPrivateKey)>,
+    ) -> Result<X509> {
+        use openssl::{
+            asn1::Asn1Time,
+            x509::{extension::BasicConstraints, X509Builder, X509NameBuilder},
+        };
+
+        fn pkey(k: &EcdsaPrivateKey) -> Result<PKey<openssl::pkey::Private>> {
+            Ok(PKey::private_key_from_pem(
+                k.private_key_to_pem_pkcs8()?.as_bytes(),
+            )?)
+        }
+
+        let key = pkey(key)?;
+        let mut name = X509NameBuilder::new()?;
+        name.append_entry_by_text("CN", subject)?;
+        let name = name.build();
+        let mut cert = X509Builder::new()?;
+        cert.set_version(2)?;
+        cert.set_subject_name(&name)?;
+        cert.set_pubkey(&key)?;
+        cert.set_not_before(Asn1Time::days_from_now(0)?.as_ref())?;
+        cert.set_not_after(Asn1Time::days_from_now(1)?.as_ref())?;
+        match issuer {
+            Some((issuer_cert, issuer_key)) => {
+                cert.set_issuer_name(issuer_cert.subject_name())?;
+                let issuer_key = pkey(issuer_key)?;
+                cert.sign(&issuer_key, MessageDigest::sha256())?;
+            }
+            None => {
+                cert.append_extension(BasicConstraints::new().critical().ca().build()?)?;
+                cert.set_issuer_name(&name)?;
+                cert.sign(&key, MessageDigest::sha256())?;
+            }
+        }
+        Ok(cert.build())
+    }
+
+    #[test]
+    fn test_jwks_x5c_chain() -> Result<()> {
+        use openssl::x509::store::X509StoreBuilder;
+
+        let root_key = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let root = make_cert("root", &root_key, None)?;
+        let other_root_key = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let other_root = make_cert("other root", &other_root_key, None)?;
+
+        let k = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let leaf = make_cert("leaf", &k, Some((&root, &root_key)))?;
+        let jwk = Jwk {
+            kid: Some("leaf".into()),
+            x5c: vec![base64::encode(leaf.to_der()?)],
+            ..k.public_key_to_jwk()?
+        };
+        let jwks: JwkSet =
+            serde_json::from_str(&serde_json::to_string(&JwkSet { keys: vec![jwk] })?)?;
+        assert_eq!(jwks.keys[0].x5c.len(), 1);
+
+        let mut trusted = X509StoreBuilder::new()?;
+        trusted.add_cert(root)?;
+        let trusted = trusted.build();
+        let mut untrusted = X509StoreBuilder::new()?;
+        untrusted.add_cert(other_root)?;
+        let untrusted = untrusted.build();
+
+        jwks.keys[0].verify_x5c_chain(&trusted)?;
+        assert!(matches!(
+            jwks.keys[0].verify_x5c_chain(&untrusted),
+            Err(Error::InvalidCertificateChain)
+        ));
+
+        let token = sign(HeaderAndClaims::new_dynamic().set_kid("leaf"), &k)?;
+        jwks.verifier_with_x5c_chain(&trusted)
+            .verify::<Map<String, Value>>(&token)?;
+        assert!(matches!(
+            jwks.verifier_with_x5c_chain(&untrusted)
+                .verify::<Map<String, Value>>(&token),
+            Err(Error::NoKey)
+        ));
+
+        // Valid chain, but for a different key.
+        let other = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let mismatched = Jwk {
+            x5c: jwks.keys[0].x5c.clone(),
+            ..other.public_key_to_jwk()?
+        };
+        assert!(matches!(
+            mismatched.verify_x5c_chain(&trusted),
+            Err(Error::InvalidCertificateChain)
+        ));
+
+        // No chain at all.
+        assert!(matches!(
+            other.public_key_to_jwk()?.verify_x5c_chain(&trusted),
+            Err(Error::InvalidCertificateChain)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_offline_verifier() -> Result<()> {
+        let k = WithKid::new("k".into(), Ed25519PrivateKey::generate()?);
+        let other = WithKid::new("other".into(), Ed25519PrivateKey::generate()?);
+
+        let mut keys = HashMap::new();
+        keys.insert(
+            "k".to_string(),
+            k.public_key_to_jwk()?.to_verification_key()?,
+        );
+        let jwks = JwkSet {
+            keys: vec![k.public_key_to_jwk()?],
+        };
+
+        for verifier in [
+            OfflineVerifier::from_keys(keys),
+            OfflineVerifier::new(&jwks),
+        ] {
+            verifier
+                .verify::<Map<String, Value>>(&sign(&mut HeaderAndClaims::new_dynamic(), &k)?)?;
+            assert!(matches!(
+                verifier.verify::<Map<String, Value>>(&sign(
+                    &mut HeaderAndClaims::new_dynamic(),
+                    &other
+                )?),
+                Err(Error::NoKey)
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "rsa")]
+    #[test]
+    fn test_implied_alg() -> Result<()> {
+        let jwk = |kty: &str, crv: Option<&str>, alg: Option<&str>| Jwk {
+            kty: kty.into(),
+            crv: crv.map(Into::into),
+            alg: alg.map(Into::into),
+            ..Default::default()
+        };
+
+        assert_eq!(jwk("EC", Some("P-256"), None).implied_alg()?, "ES256");
+        assert_eq!(jwk("EC", Some("P-384"), None).implied_alg()?, "ES384");
+        assert_eq!(jwk("EC", Some("secp256k1"), None).implied_alg()?, "ES256K");
+        assert!(jwk("EC", None, None).implied_alg().is_err());
+        assert!(jwk("EC", Some("P-192"), None).implied_alg().is_err());
+
+        assert_eq!(jwk("RSA", None, Some("PS384")).implied_alg()?, "PS384");
+        assert_eq!(jwk("RSA", None, None).implied_alg()?, "RS256");
+        assert_eq!(jwk("RSA", None, Some("x-vendor")).implied_alg()?, "RS256");
+
+        assert_eq!(jwk("OKP", Some("Ed25519"), None).implied_alg()?, "EdDSA");
+        assert_eq!(jwk("OKP", Some("ed25519"), None).implied_alg()?, "EdDSA");
+        assert!(jwk("OKP", Some("X25519"), None).implied_alg().is_err());
+
+        assert!(jwk("oct", None, Some("HS256")).implied_alg().is_err());
+
+        // Agrees with the constructed key.
+        let k = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES512)?;
+        assert_eq!(k.public_key_to_jwk()?.implied_alg()?, k.alg());
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwk_export_options() -> Result<()> {
+        let k = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let default = k.public_key_to_jwk_with_options(&JwkExportOptions::new())?;
+        assert_eq!(
+            serde_json::to_value(&default)?,
+            serde_json::to_value(&k.public_key_to_jwk()?)?
+        );
+
+        let jwk = k.public_key_to_jwk_with_options(
+            JwkExportOptions::new()
+                .set_use(None)
+                .set_key_ops(vec!["verify".into()])
+                .set_include_alg(false),
+        )?;
+        assert_eq!(jwk.use_, None);
+        assert_eq!(jwk.key_ops, ["verify"]);
+        assert_eq!(jwk.alg, None);
+        jwk.to_verification_key()?;
+
+        // Through `WithKid` too.
+        let jwk = WithKid::new("k".into(), k).public_key_to_jwk_with_options(
+            JwkExportOptions::new().set_key_ops(vec!["verify".into()]),
+        )?;
+        assert_eq!(jwk.kid.as_deref(), Some("k"));
+        assert_eq!(jwk.use_.as_deref(), Some("sig"));
+        assert_eq!(jwk.key_ops, ["verify"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_es256k_jwk_round_trip() -> Result<()> {
+        let k = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256K)?;
+        let jwk = k.public_key_to_jwk()?;
+        assert_eq!(jwk.alg.as_deref(), Some("ES256K"));
+        assert_eq!(jwk.crv.as_deref(), Some("secp256k1"));
+
+        let jwk: Jwk = serde_json::from_str(&serde_json::to_string(&jwk)?)?;
+        let pk = jwk.to_verification_key()?;
+        let token = sign(&mut HeaderAndClaims::new_dynamic(), &k)?;
+        let decoded = verify::<Map<String, Value>>(&token, &pk)?;
+        assert_eq!(decoded.header().alg, "ES256K");
+
+        // `alg` must agree with `crv`.
+        let mismatched = Jwk {
+            alg: Some("ES256".into()),
+            ..jwk
+        };
+        assert!(mismatched.to_verification_key().is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "rsa")]
+    #[test]
+    fn test_thumbprint() -> Result<()> {
+        RsaPrivateKey::generate(2048, RsaAlgorithm::RS256)?
+            .public_key_to_jwk()?
+            .get_thumbprint_sha256_base64()?;
+        EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?
+            .public_key_to_jwk()?
+            .get_thumbprint_sha256_base64()?;
+        Ed25519PrivateKey::generate()?
+            .public_key_to_jwk()?
+            .get_thumbprint_sha256_base64()?;
+        Ok(())
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct MyClaim {
+        foo: String,
+    }
+
+    #[test]
+    fn test_jwks_verify() -> Result<()> {
+        let k = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES512)?;
+        let kk = WithKid::new("my key".into(), k.clone());
+        let k_jwk = kk.public_key_to_jwk()?;
+        let jwks = JwkSet { keys: vec![k_jwk] };
+        let mut verifier = jwks.verifier();
+
+        // jwt with kid
         {
             let mut jwt = HeaderAndClaims::with_claims(MyClaim { foo: "bar".into() });
             jwt.set_kid("my key");
@@ -657,4 +2817,787 @@ mod tests {
 
         Ok(())
     }
+
+    /// Serve `handler()` as JSON on a local http url. Returns the url and the
+    /// number of requests served so far.
+    #[cfg(feature = "remote-jwks")]
+    fn serve_json(
+        handler: impl Fn() -> String + Send + 'static,
+    ) -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+        let (base, count) = serve_json_paths(move |_| Some(handler()));
+        (format!("{}/jwks", base), count)
+    }
+
+    /// Serve `handler(path)` as JSON on a local http server, or 404 if it
+    /// returns `None`. Returns the base url and the number of requests served
+    /// so far.
+    #[cfg(feature = "remote-jwks")]
+    fn serve_json_paths(
+        handler: impl Fn(&str) -> Option<String> + Send + 'static,
+    ) -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+        use std::{
+            io::{Read, Write},
+            net::TcpListener,
+            sync::{atomic::AtomicUsize, atomic::Ordering, Arc},
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let count = Arc::new(AtomicUsize::new(0));
+        let count1 = count.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut req = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !req.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    req.extend_from_slice(&buf[..n]);
+                }
+                count1.fetch_add(1, Ordering::SeqCst);
+                // `GET /path HTTP/1.1`
+                let req = String::from_utf8_lossy(&req);
+                let path = req.split(' ').nth(1).unwrap_or_default();
+                let (status, body) = match handler(path) {
+                    Some(body) => ("200 OK", body),
+                    None => ("404 Not Found", String::new()),
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+            }
+        });
+        (url, count)
+    }
+
+    #[cfg(feature = "remote-jwks")]
+    #[tokio::test]
+    async fn test_remote_jwks_cache_ttl_remaining() -> Result<()> {
+        use std::time::Duration;
+
+        let k = WithKid::new(
+            "k".into(),
+            EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?,
+        );
+        let jwks = serde_json::to_string(&JwkSet {
+            keys: vec![k.public_key_to_jwk()?],
+        })?;
+        let (url, count) = serve_json(move || jwks.clone());
+        let token = sign(&mut HeaderAndClaims::new_dynamic(), &k)?;
+
+        let v = RemoteJwksVerifier::builder(url)
+            .allow_insecure_http(true)
+            .cache_duration(Duration::from_millis(500))
+            .build()?;
+        assert_eq!(v.cache_ttl_remaining().await, None);
+
+        v.verify::<Map<String, Value>>(&token).await?;
+        let ttl = v.cache_ttl_remaining().await.unwrap();
+        assert!(ttl <= Duration::from_millis(500));
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let later = v.cache_ttl_remaining().await.unwrap();
+        assert!(later < ttl);
+
+        tokio::time::sleep(Duration::from_millis(450)).await;
+        assert_eq!(v.cache_ttl_remaining().await, Some(Duration::ZERO));
+
+        // Refreshed on fetch.
+        v.verify::<Map<String, Value>>(&token).await?;
+        assert_eq!(count.load(std::sync::atomic::Ordering::SeqCst), 2);
+        assert!(v.cache_ttl_remaining().await.unwrap() > later);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "remote-jwks")]
+    #[test]
+    fn test_cache_lifetime() {
+        use reqwest::header::{HeaderMap, HeaderValue, CACHE_CONTROL, DATE, EXPIRES};
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let headers = |pairs: &[(reqwest::header::HeaderName, &str)]| {
+            let mut h = HeaderMap::new();
+            for (name, value) in pairs {
+                h.insert(name, HeaderValue::from_str(value).unwrap());
+            }
+            h
+        };
+        let in_an_hour = httpdate::fmt_http_date(now + Duration::from_secs(3600));
+        let in_an_hour = &*in_an_hour;
+        let date = httpdate::fmt_http_date(now + Duration::from_secs(600));
+
+        // max-age only.
+        assert_eq!(
+            cache_lifetime(
+                &headers(&[(CACHE_CONTROL, "public, Max-Age=600, must-revalidate")]),
+                now
+            ),
+            Some(Duration::from_secs(600))
+        );
+        // Expires only.
+        assert_eq!(
+            cache_lifetime(&headers(&[(EXPIRES, in_an_hour)]), now),
+            Some(Duration::from_secs(3600))
+        );
+        // Expires relative to Date.
+        assert_eq!(
+            cache_lifetime(
+                &headers(&[(EXPIRES, in_an_hour), (DATE, &date)]),
+                SystemTime::now()
+            ),
+            Some(Duration::from_secs(3000))
+        );
+        // Both, max-age wins.
+        assert_eq!(
+            cache_lifetime(
+                &headers(&[(CACHE_CONTROL, "max-age=60"), (EXPIRES, in_an_hour)]),
+                now
+            ),
+            Some(Duration::from_secs(60))
+        );
+        // Invalid max-age falls back to Expires.
+        assert_eq!(
+            cache_lifetime(
+                &headers(&[(CACHE_CONTROL, "max-age=soon"), (EXPIRES, in_an_hour)]),
+                now
+            ),
+            Some(Duration::from_secs(3600))
+        );
+        // Expired, like absent.
+        assert_eq!(
+            cache_lifetime(&headers(&[(EXPIRES, "Thu, 01 Jan 1970 00:00:00 GMT")]), now),
+            None
+        );
+        assert_eq!(
+            cache_lifetime(&headers(&[(EXPIRES, in_an_hour), (DATE, in_an_hour)]), now),
+            None
+        );
+        assert_eq!(
+            cache_lifetime(&headers(&[(CACHE_CONTROL, "no-store, max-age=0")]), now),
+            None
+        );
+        // Capped.
+        assert_eq!(
+            cache_lifetime(
+                &headers(&[(CACHE_CONTROL, "max-age=18446744073709551615")]),
+                now
+            ),
+            Some(MAX_CACHE_LIFETIME)
+        );
+        assert_eq!(
+            cache_lifetime(&headers(&[(EXPIRES, "Fri, 31 Dec 9999 23:59:59 GMT")]), now),
+            Some(MAX_CACHE_LIFETIME)
+        );
+        // Absent or invalid.
+        assert_eq!(cache_lifetime(&headers(&[]), now), None);
+        assert_eq!(
+            cache_lifetime(
+                &headers(&[(CACHE_CONTROL, "no-cache"), (EXPIRES, "0")]),
+                now
+            ),
+            None
+        );
+    }
+
+    #[cfg(feature = "remote-jwks")]
+    #[tokio::test]
+    async fn test_remote_jwks_discovery_url() -> Result<()> {
+        let k = WithKid::new(
+            "k".into(),
+            EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?,
+        );
+        let jwks = serde_json::to_string(&JwkSet {
+            keys: vec![k.public_key_to_jwk()?],
+        })?;
+        let base = std::sync::Arc::new(std::sync::Mutex::new(String::new()));
+        let base1 = base.clone();
+        let (url, _) = serve_json_paths(move |path| match path {
+            "/custom/discovery" => Some(
+                serde_json::json!({
+                    "issuer": "https://issuer.example",
+                    "jwks_uri": format!("{}/keys", base1.lock().unwrap()),
+                })
+                .to_string(),
+            ),
+            "/keys" => Some(jwks.clone()),
+            _ => None,
+        });
+        *base.lock().unwrap() = url.clone();
+        let discovery_url = format!("{}/custom/discovery", url);
+        let cache_duration = std::time::Duration::from_secs(60);
+        let token = sign(&mut HeaderAndClaims::new_dynamic(), &k)?;
+
+        for expected_issuer in [None, Some("https://issuer.example")] {
+            let v = RemoteJwksVerifier::from_discovery_url_allow_insecure_http(
+                &discovery_url,
+                expected_issuer,
+                None,
+                cache_duration,
+            )
+            .await?;
+            v.verify::<Map<String, Value>>(&token).await?;
+        }
+        assert!(matches!(
+            RemoteJwksVerifier::from_discovery_url_allow_insecure_http(
+                &discovery_url,
+                Some("https://other.example"),
+                None,
+                cache_duration,
+            )
+            .await,
+            Err(Error::InvalidIssuer)
+        ));
+        // The standard path is not served.
+        assert!(matches!(
+            RemoteJwksVerifier::from_issuer_allow_insecure_http(&url, None, cache_duration).await,
+            Err(Error::Reqwest(_))
+        ));
+        assert!(matches!(
+            RemoteJwksVerifier::from_discovery_url(&discovery_url, None, None, cache_duration)
+                .await,
+            Err(Error::InsecureUrl)
+        ));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "remote-jwks")]
+    #[tokio::test]
+    async fn test_remote_jwks_arc_swap() -> Result<()> {
+        let k = WithKid::new(
+            "k".into(),
+            EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?,
+        );
+        let jwks = serde_json::to_string(&JwkSet {
+            keys: vec![k.public_key_to_jwk()?],
+        })?;
+        let (url, count) = serve_json(move || jwks.clone());
+
+        let v = RemoteJwksVerifier::new_allow_insecure_http(
+            url,
+            None,
+            std::time::Duration::from_millis(200),
+        );
+        let token = sign(&mut HeaderAndClaims::new_dynamic(), &k)?;
+
+        let set0 = v.jwk_set().await?;
+        v.verify::<Map<String, Value>>(&token).await?;
+        let set1 = v.jwk_set().await?;
+        assert!(std::sync::Arc::ptr_eq(&set0, &set1));
+        assert_eq!(count.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        let set2 = v.jwk_set().await?;
+        assert!(!std::sync::Arc::ptr_eq(&set0, &set2));
+        assert_eq!(count.load(std::sync::atomic::Ordering::SeqCst), 2);
+        // The old set is still usable by whoever holds it.
+        assert_eq!(set0.keys[0].kid.as_deref(), Some("k"));
+        v.verify::<Map<String, Value>>(&token).await?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "remote-jwks")]
+    #[tokio::test]
+    async fn test_remote_jwks_last_fetch_time() -> Result<()> {
+        let jwks = serde_json::to_string(&JwkSet { keys: vec![] })?;
+        let (url, _) = serve_json(move || jwks.clone());
+        let v = RemoteJwksVerifier::new_allow_insecure_http(
+            url,
+            None,
+            std::time::Duration::from_millis(100),
+        );
+        assert_eq!(v.last_fetch_time().await, None);
+
+        let before = std::time::SystemTime::now();
+        v.jwk_set().await?;
+        let t0 = v.last_fetch_time().await.unwrap();
+        assert!(t0 >= before);
+        // Cached.
+        v.jwk_set().await?;
+        assert_eq!(v.last_fetch_time().await, Some(t0));
+
+        tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+        v.jwk_set().await?;
+        assert!(v.last_fetch_time().await.unwrap() > t0);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "remote-jwks")]
+    #[tokio::test]
+    async fn test_remote_jwks_key_source() -> Result<()> {
+        let k = WithKid::new(
+            "k".into(),
+            EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?,
+        );
+        let jwks = serde_json::to_string(&JwkSet {
+            keys: vec![k.public_key_to_jwk()?],
+        })?;
+        let (url, _) = serve_json(move || jwks.clone());
+
+        let v = RemoteJwksVerifier::new_allow_insecure_http(
+            url,
+            None,
+            std::time::Duration::from_secs(300),
+        );
+        let token = sign(&mut HeaderAndClaims::new_dynamic(), &k)?;
+        let options = VerifyOptions::new();
+
+        let verified = v
+            .verify_with_options::<Map<String, Value>>(&token, &options)
+            .await?;
+        assert_eq!(verified.key_source(), Some(KeySource::Fetched));
+        let verified = v
+            .verify_with_options::<Map<String, Value>>(&token, &options)
+            .await?;
+        assert_eq!(verified.key_source(), Some(KeySource::Cache));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "remote-jwks")]
+    #[tokio::test]
+    async fn test_remote_jwks_unknown_kid_refetch() -> Result<()> {
+        use std::sync::{atomic::Ordering, Arc, Mutex};
+
+        let k1 = WithKid::new(
+            "k1".into(),
+            EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?,
+        );
+        let k2 = WithKid::new(
+            "k2".into(),
+            EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?,
+        );
+        let jwks = Arc::new(Mutex::new(serde_json::to_string(&JwkSet {
+            keys: vec![k1.public_key_to_jwk()?],
+        })?));
+        let jwks1 = jwks.clone();
+        let (url, count) = serve_json(move || jwks1.lock().unwrap().clone());
+
+        let mut v = RemoteJwksVerifier::new_allow_insecure_http(
+            url,
+            None,
+            std::time::Duration::from_secs(300),
+        );
+        v.set_unknown_kid_refetch_interval(std::time::Duration::from_millis(300));
+
+        let token1 = sign(&mut HeaderAndClaims::new_dynamic(), &k1)?;
+        let token2 = sign(&mut HeaderAndClaims::new_dynamic(), &k2)?;
+        let k3 = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let unknown = |i: usize| {
+            sign(
+                HeaderAndClaims::new_dynamic().set_kid(format!("unknown-{}", i)),
+                &k3,
+            )
+        };
+
+        v.verify::<Map<String, Value>>(&token1).await?;
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        // Key rotated on the server.
+        *jwks.lock().unwrap() = serde_json::to_string(&JwkSet {
+            keys: vec![k1.public_key_to_jwk()?, k2.public_key_to_jwk()?],
+        })?;
+
+        // Within the window unknown kids don't cause fetches.
+        for i in 0..50 {
+            assert!(matches!(
+                v.verify::<Map<String, Value>>(&unknown(i)?).await,
+                Err(Error::NoKey)
+            ));
+        }
+        assert!(matches!(
+            v.verify::<Map<String, Value>>(&token2).await,
+            Err(Error::NoKey)
+        ));
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        // After the window, one refetch picks up the new key.
+        tokio::time::sleep(std::time::Duration::from_millis(400)).await;
+        v.verify::<Map<String, Value>>(&token2).await?;
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        for i in 0..50 {
+            assert!(v.verify::<Map<String, Value>>(&unknown(i)?).await.is_err());
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        tokio::time::sleep(std::time::Duration::from_millis(400)).await;
+        for i in 0..50 {
+            assert!(v.verify::<Map<String, Value>>(&unknown(i)?).await.is_err());
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+
+        // Failed refetches are rate limited too.
+        *jwks.lock().unwrap() = "server error".into();
+        tokio::time::sleep(std::time::Duration::from_millis(400)).await;
+        for i in 0..50 {
+            assert!(v.verify::<Map<String, Value>>(&unknown(i)?).await.is_err());
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 4);
+        v.verify::<Map<String, Value>>(&token2).await?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "remote-jwks")]
+    #[tokio::test]
+    async fn test_remote_jwks_rotation_single_flight() -> Result<()> {
+        use std::sync::{atomic::Ordering, Arc, Mutex};
+
+        let k1 = WithKid::new(
+            "k1".into(),
+            EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?,
+        );
+        let k2 = WithKid::new(
+            "k2".into(),
+            EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?,
+        );
+        let jwks = Arc::new(Mutex::new(serde_json::to_string(&JwkSet {
+            keys: vec![k1.public_key_to_jwk()?],
+        })?));
+        let jwks1 = jwks.clone();
+        let (url, count) = serve_json(move || jwks1.lock().unwrap().clone());
+
+        let mut v = RemoteJwksVerifier::new_allow_insecure_http(
+            url,
+            None,
+            std::time::Duration::from_secs(300),
+        );
+        v.set_unknown_kid_refetch_interval(std::time::Duration::from_millis(100));
+        let v = Arc::new(v);
+
+        v.jwk_set().await?;
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        *jwks.lock().unwrap() = serde_json::to_string(&JwkSet {
+            keys: vec![k1.public_key_to_jwk()?, k2.public_key_to_jwk()?],
+        })?;
+        tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+
+        // The first lookup of k2 misses the cached set, a single refresh adds
+        // it and every retry succeeds.
+        let token = sign(&mut HeaderAndClaims::new_dynamic(), &k2)?;
+        let tasks: Vec<_> = (0..20)
+            .map(|_| {
+                let v = v.clone();
+                let token = token.clone();
+                tokio::spawn(async move {
+                    v.verify_with_options::<Map<String, Value>>(&token, &VerifyOptions::new())
+                        .await
+                })
+            })
+            .collect();
+        for t in tasks {
+            t.await.unwrap()?;
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "remote-jwks")]
+    #[tokio::test]
+    async fn test_remote_jwks_refetch_does_not_block_cached_keys() -> Result<()> {
+        use std::sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        };
+        use std::time::Duration;
+
+        let k = WithKid::new(
+            "k".into(),
+            EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?,
+        );
+        let jwks = serde_json::to_string(&JwkSet {
+            keys: vec![k.public_key_to_jwk()?],
+        })?;
+        let hang = Arc::new(AtomicBool::new(false));
+        let hang1 = hang.clone();
+        let (url, count) = serve_json(move || {
+            if hang1.load(Ordering::SeqCst) {
+                std::thread::sleep(Duration::from_millis(1000));
+            }
+            jwks.clone()
+        });
+
+        let mut v =
+            RemoteJwksVerifier::new_allow_insecure_http(url, None, Duration::from_secs(300));
+        v.set_unknown_kid_refetch_interval(Duration::from_millis(100));
+        let v = Arc::new(v);
+        let token = sign(&mut HeaderAndClaims::new_dynamic(), &k)?;
+        v.verify::<Map<String, Value>>(&token).await?;
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        hang.store(true, Ordering::SeqCst);
+        let unknown = sign(
+            HeaderAndClaims::new_dynamic().set_kid("unknown"),
+            &EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?,
+        )?;
+        let refetch = {
+            let v = v.clone();
+            tokio::spawn(async move { v.verify::<Map<String, Value>>(&unknown).await })
+        };
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        // The refetch hangs, but the cached key still verifies right away.
+        tokio::time::timeout(
+            Duration::from_millis(300),
+            v.verify::<Map<String, Value>>(&token),
+        )
+        .await
+        .expect("blocked by the refetch")?;
+
+        assert!(matches!(refetch.await.unwrap(), Err(Error::NoKey)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_jwks() -> Result<()> {
+        let ec = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let ed = Ed25519PrivateKey::generate()?;
+        let weak_rsa = openssl::rsa::Rsa::generate(1024)?;
+        let weak_rsa = Jwk {
+            kty: "RSA".into(),
+            kid: Some("weak".into()),
+            n: Some(base64::encode_config(
+                weak_rsa.n().to_vec(),
+                url_safe_trailing_bits(),
+            )),
+            e: Some(base64::encode_config(
+                weak_rsa.e().to_vec(),
+                url_safe_trailing_bits(),
+            )),
+            ..Jwk::default()
+        };
+        let jwks = serde_json::json!({
+            "keys": [
+                // 0: good.
+                Jwk { kid: Some("ec".into()), ..ec.public_key_to_jwk()? },
+                // 1: duplicate kid.
+                Jwk { kid: Some("ec".into()), ..ed.public_key_to_jwk()? },
+                // 2: missing kid.
+                ed.public_key_to_jwk()?,
+                // 3: encryption key.
+                Jwk { kid: Some("enc".into()), use_: Some("enc".into()), ..ec.public_key_to_jwk()? },
+                // 4: weak.
+                weak_rsa,
+                // 5: unparsable.
+                { "kty": "EC", "kid": 5 },
+                // 6: unsupported.
+                { "kty": "oct", "kid": "oct", "k": "AAAA" },
+            ]
+        });
+        let report = validate_jwks(&jwks.to_string())?;
+        assert_eq!(report.num_keys, 7);
+        assert_eq!(report.signing_keys, [0, 1, 2, 4, 6]);
+        assert_eq!(report.encryption_keys, [3]);
+        assert_eq!(
+            report.invalid_keys.iter().map(|k| k.0).collect::<Vec<_>>(),
+            [4, 5, 6]
+        );
+        assert_eq!(report.duplicate_kids, ["ec"]);
+        assert_eq!(report.weak_rsa_keys, [4]);
+        assert_eq!(report.missing_kids, [2]);
+        assert!(!report.is_healthy());
+
+        let jwks = serde_json::to_string(&JwkSet {
+            keys: vec![Jwk {
+                kid: Some("ec".into()),
+                ..ec.public_key_to_jwk()?
+            }],
+        })?;
+        assert!(validate_jwks(&jwks)?.is_healthy());
+        assert!(validate_jwks("[]").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verifiers_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<JwkSetVerifier>();
+        #[cfg(feature = "remote-jwks")]
+        assert_send_sync::<RemoteJwksVerifier>();
+    }
+
+    #[cfg(feature = "remote-jwks")]
+    #[test]
+    fn test_remote_jwks_url_scheme() {
+        let dur = std::time::Duration::from_secs(300);
+        assert!(RemoteJwksVerifier::new("https://example.com/jwks".into(), None, dur).is_ok());
+        assert!(matches!(
+            RemoteJwksVerifier::new("http://example.com/jwks".into(), None, dur),
+            Err(Error::InsecureUrl)
+        ));
+        assert!(matches!(
+            RemoteJwksVerifier::new("example.com/jwks".into(), None, dur),
+            Err(Error::InsecureUrl)
+        ));
+        RemoteJwksVerifier::new_allow_insecure_http("http://example.com/jwks".into(), None, dur);
+    }
+
+    #[cfg(feature = "remote-jwks")]
+    #[tokio::test]
+    async fn test_remote_jwks_builder() -> Result<()> {
+        let k = WithKid::new(
+            "k".into(),
+            EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?,
+        );
+        let jwks = serde_json::to_string(&JwkSet {
+            keys: vec![k.public_key_to_jwk()?],
+        })?;
+        let (url, _) = serve_json(move || jwks.clone());
+
+        assert!(matches!(
+            RemoteJwksVerifier::builder(url.clone()).build(),
+            Err(Error::InsecureUrl)
+        ));
+        assert!(matches!(
+            RemoteJwksVerifier::builder(url.clone())
+                .allow_insecure_http(true)
+                .unknown_kid_refetch_interval(std::time::Duration::ZERO)
+                .build(),
+            Err(Error::InvalidConfig(_))
+        ));
+
+        let v = RemoteJwksVerifier::builder(url)
+            .allow_insecure_http(true)
+            .client(reqwest::Client::new())
+            .cache_duration(std::time::Duration::from_secs(60))
+            .unknown_kid_refetch_interval(std::time::Duration::from_secs(5))
+            .pinned_key(k.public_key_to_jwk()?.to_verification_key()?)
+            .uniform_failure(true)
+            .build()?;
+        let token = sign(&mut HeaderAndClaims::new_dynamic(), &k)?;
+        v.verify::<Map<String, Value>>(&token).await?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "remote-jwks")]
+    #[tokio::test]
+    async fn test_remote_jwks_timeout() -> Result<()> {
+        use std::sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        };
+
+        let k = WithKid::new(
+            "k".into(),
+            EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?,
+        );
+        let jwks = serde_json::to_string(&JwkSet {
+            keys: vec![k.public_key_to_jwk()?],
+        })?;
+        let slow = Arc::new(AtomicBool::new(false));
+        let slow1 = slow.clone();
+        let (url, count) = serve_json(move || {
+            if slow1.load(Ordering::SeqCst) {
+                std::thread::sleep(std::time::Duration::from_millis(500));
+            }
+            jwks.clone()
+        });
+        let token = sign(&mut HeaderAndClaims::new_dynamic(), &k)?;
+
+        let v = RemoteJwksVerifier::builder(url.clone())
+            .allow_insecure_http(true)
+            .cache_duration(std::time::Duration::from_millis(100))
+            .timeout(std::time::Duration::from_millis(200))
+            .build()?;
+        v.verify::<Map<String, Value>>(&token).await?;
+
+        // The cache expired and the fetch times out: serve the stale key set.
+        slow.store(true, Ordering::SeqCst);
+        tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+        v.verify::<Map<String, Value>>(&token).await?;
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        // Later verifications during the outage don't wait for the timeout.
+        let start = std::time::Instant::now();
+        for _ in 0..5 {
+            v.verify::<Map<String, Value>>(&token).await?;
+        }
+        assert!(start.elapsed() < std::time::Duration::from_millis(100));
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        // Nothing cached.
+        let v = RemoteJwksVerifier::builder(url)
+            .allow_insecure_http(true)
+            .timeout(std::time::Duration::from_millis(200))
+            .build()?;
+        assert!(matches!(
+            v.verify::<Map<String, Value>>(&token).await,
+            Err(Error::FetchTimeout)
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_with_jwks_fn() -> Result<()> {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        let k = WithKid::new(
+            "k".into(),
+            EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?,
+        );
+        let jwks = Arc::new(JwkSet {
+            keys: vec![k.public_key_to_jwk()?],
+        });
+        let calls = AtomicUsize::new(0);
+        let get_keys = || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            let jwks = jwks.clone();
+            async move { Ok(jwks) }
+        };
+        let options = VerifyOptions::new();
+
+        let token = sign(HeaderAndClaims::new_dynamic().set_sub("a"), &k)?;
+        let verified =
+            verify_with_jwks_fn::<Map<String, Value>, _, _>(&token, get_keys, &options).await?;
+        assert_eq!(
+            verified.header_and_claims().claims().sub.as_deref(),
+            Some("a")
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let other = sign(
+            &mut HeaderAndClaims::new_dynamic(),
+            &EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?,
+        )?;
+        let results = verify_batch_with_jwks_fn::<Map<String, Value>, _, _>(
+            &[&token, &other, &token],
+            get_keys,
+            &options,
+        )
+        .await?;
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+        assert!(results[2].is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        assert!(verify_with_jwks_fn::<Map<String, Value>, _, _>(
+            &token,
+            || async { Err(Error::NoKey) },
+            &options
+        )
+        .await
+        .is_err());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,197 @@
+//! Key policy.
+
+use crate::{Error, Result, SomePublicKey};
+
+/// Restrictions on accepted keys and algorithms, e.g. for compliance.
+///
+/// Keys are checked when loaded with `Jwk::to_verification_key_with_policy`
+/// or `JwkSet::verifier_with_policy`. Token algorithms, and keys selected from
+/// a JWK set, are checked at verification time if the policy is set with
+/// `VerifyOptions::set_key_policy`.
+///
+/// Everything is allowed by default.
+#[non_exhaustive]
+#[derive(Debug, Default, Clone)]
+pub struct KeyPolicy {
+    curves: Option<Vec<String>>,
+    min_rsa_bits: u32,
+    algs: Option<Vec<String>>,
+}
+
+impl KeyPolicy {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only allow EC/Ed25519 keys on these curves, by JWK `crv` name, e.g.
+    /// `P-384` or `Ed25519`.
+    #[inline]
+    pub fn set_allowed_curves(&mut self, curves: Vec<String>) -> &mut Self {
+        self.curves = Some(curves);
+        self
+    }
+
+    /// Only allow RSA keys with a modulus of at least `bits` bits.
+    ///
+    /// RSA keys shorter than 2048 bits are never accepted.
+    #[inline]
+    pub fn set_min_rsa_bits(&mut self, bits: u32) -> &mut Self {
+        self.min_rsa_bits = bits;
+        self
+    }
+
+    /// Only allow these algorithms, e.g. `ES384` or `PS256`.
+    #[inline]
+    pub fn set_allowed_algs(&mut self, algs: Vec<String>) -> &mut Self {
+        self.algs = Some(algs);
+        self
+    }
+
+    /// Fails with `Error::KeyPolicyViolation` if `alg` is not allowed.
+    pub fn check_alg(&self, alg: &str) -> Result<()> {
+        match self.algs {
+            Some(ref algs) if !algs.iter().any(|a| a == alg) => Err(Error::KeyPolicyViolation(
+                format!("alg {} is not allowed", alg),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    #[cfg(any(feature = "ecdsa", feature = "eddsa"))]
+    fn check_curve(&self, crv: &str) -> Result<()> {
+        match self.curves {
+            Some(ref curves) if !curves.iter().any(|c| c == crv) => Err(Error::KeyPolicyViolation(
+                format!("curve {} is not allowed", crv),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails with `Error::KeyPolicyViolation` if the curve, size or algorithm
+    /// of `k` is not allowed.
+    ///
+    /// RSA keys without an algorithm only have their size checked here.
+    pub fn check_key(&self, k: &SomePublicKey) -> Result<()> {
+        match *k {
+            #[cfg(feature = "eddsa")]
+            SomePublicKey::Ed25519(_) => {
+                self.check_curve("Ed25519")?;
+                self.check_alg("EdDSA")
+            }
+            #[cfg(feature = "ecdsa")]
+            SomePublicKey::Ecdsa(ref ec) => {
+                self.check_curve(ec.algorithm().curve_name())?;
+                self.check_alg(ec.algorithm().name())
+            }
+            #[cfg(feature = "rsa")]
+            SomePublicKey::Rsa(ref rsa) => {
+                let bits = rsa.bits();
+                if bits < self.min_rsa_bits {
+                    return Err(Error::KeyPolicyViolation(format!(
+                        "RSA key has {} bits, at least {} are required",
+                        bits, self.min_rsa_bits
+                    )));
+                }
+                match rsa.algorithm {
+                    Some(alg) => self.check_alg(alg.name()),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "sign", any(feature = "ecdsa", feature = "rsa")))]
+mod tests {
+    use serde_json::{Map, Value};
+
+    use crate::{sign, HeaderAndClaims, PublicKeyToJwk, VerifyOptions};
+
+    use super::*;
+
+    #[cfg(feature = "ecdsa")]
+    #[test]
+    fn forbid_p256() -> Result<()> {
+        use crate::{
+            ecdsa::{EcdsaAlgorithm, EcdsaPrivateKey},
+            jwk::{JwkSet, WithKid},
+        };
+
+        let mut policy = KeyPolicy::new();
+        policy.set_allowed_curves(vec!["P-384".into(), "Ed25519".into()]);
+
+        let p256 = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let p384 = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES384)?;
+        assert!(matches!(
+            p256.public_key_to_jwk()?
+                .to_verification_key_with_policy(&policy),
+            Err(Error::KeyPolicyViolation(_))
+        ));
+        p384.public_key_to_jwk()?
+            .to_verification_key_with_policy(&policy)?;
+
+        let p256 = WithKid::new("p256".into(), p256);
+        let jwks = JwkSet {
+            keys: vec![p256.public_key_to_jwk()?],
+        };
+        let token = sign(&mut HeaderAndClaims::new_dynamic(), &p256)?;
+        assert!(matches!(
+            jwks.verifier_with_policy(&policy)
+                .verify::<Map<String, Value>>(&token),
+            Err(Error::NoKey)
+        ));
+
+        // Checked at verification time too.
+        let verifier = jwks.verifier();
+        verifier.verify::<Map<String, Value>>(&token)?;
+        assert!(matches!(
+            verifier.verify_with_options::<Map<String, Value>>(
+                &token,
+                VerifyOptions::new().set_key_policy(policy)
+            ),
+            Err(Error::KeyPolicyViolation(_))
+        ));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "rsa")]
+    #[test]
+    fn forbid_rsa_below_3072() -> Result<()> {
+        use crate::{
+            rsa::{RsaAlgorithm, RsaPrivateKey},
+            verify_with_options,
+        };
+
+        let mut policy = KeyPolicy::new();
+        policy.set_min_rsa_bits(3072);
+
+        let k2048 = RsaPrivateKey::generate(2048, RsaAlgorithm::RS256)?;
+        let k3072 = RsaPrivateKey::generate(3072, RsaAlgorithm::RS256)?;
+        match k2048
+            .public_key_to_jwk()?
+            .to_verification_key_with_policy(&policy)
+        {
+            Err(Error::KeyPolicyViolation(msg)) => assert!(msg.contains("2048")),
+            r => panic!("unexpected result {:?}", r),
+        }
+        let pk3072 = k3072
+            .public_key_to_jwk()?
+            .to_verification_key_with_policy(&policy)?;
+
+        // Algorithms are checked against the token header.
+        policy.set_allowed_algs(vec!["PS256".into()]);
+        let token = sign(&mut HeaderAndClaims::new_dynamic(), &k3072)?;
+        assert!(matches!(
+            verify_with_options::<Map<String, Value>>(
+                &token,
+                &pk3072,
+                VerifyOptions::new().set_key_policy(policy)
+            ),
+            Err(Error::KeyPolicyViolation(_))
+        ));
+
+        Ok(())
+    }
+}
@@ -1,17 +1,26 @@
+#[cfg(feature = "rsa")]
+use std::convert::TryFrom;
+
 /// RSASSA-PKCS1-v1_5 using SHA-256.
+#[cfg(feature = "rsa")]
+use openssl::hash::MessageDigest;
+#[cfg(all(feature = "rsa", feature = "sign"))]
+use openssl::sign::Signer;
+#[cfg(feature = "rsa")]
 use openssl::{
     bn::BigNum,
-    hash::MessageDigest,
     pkey::{Id, PKey, Private, Public},
     rsa::{Padding, Rsa},
-    sign::{RsaPssSaltlen, Signer, Verifier},
+    sign::{RsaPssSaltlen, Verifier},
 };
+#[cfg(all(feature = "rsa", feature = "sign"))]
 use smallvec::SmallVec;
 
-use crate::{
-    jwk::Jwk, url_safe_trailing_bits, Error, PrivateKeyToJwk, PublicKeyToJwk, Result, SigningKey,
-    VerificationKey,
-};
+#[cfg(feature = "rsa")]
+use crate::{jwk::Jwk, url_safe_trailing_bits, PublicKeyToJwk, VerificationKey};
+use crate::{Error, Result};
+#[cfg(all(feature = "rsa", feature = "sign"))]
+use crate::{PrivateKeyToJwk, SigningKey};
 
 /// RSA signature algorithms.
 #[non_exhaustive]
@@ -26,6 +35,12 @@ pub enum RsaAlgorithm {
 }
 
 impl RsaAlgorithm {
+    /// All RSA algorithms.
+    pub fn all() -> &'static [Self] {
+        use RsaAlgorithm::*;
+        &[RS256, RS384, RS512, PS256, PS384, PS512]
+    }
+
     pub fn is_pss(self) -> bool {
         matches!(
             self,
@@ -33,7 +48,8 @@ impl RsaAlgorithm {
         )
     }
 
-    fn digest(self) -> MessageDigest {
+    #[cfg(feature = "rsa")]
+    pub(crate) fn digest(self) -> MessageDigest {
         use RsaAlgorithm::*;
         match self {
             RS256 | PS256 => MessageDigest::sha256(),
@@ -67,20 +83,35 @@ impl RsaAlgorithm {
     }
 }
 
+impl_algorithm_name!(RsaAlgorithm);
+
 /// RSA Private Key.
 ///
 /// By default, it only verifies signatures generated by the same algorithm used
 /// for signing. If you want to verify signatures generated by any RSA
 /// algorithm, set `verify_any` to `true`.
+#[cfg(feature = "rsa")]
 #[derive(Debug, Clone)]
 pub struct RsaPrivateKey {
     private_key: PKey<Private>,
     pub algorithm: RsaAlgorithm,
     pub verify_any: bool,
+    /// PSS salt length in bytes used for signing and verification. `None`
+    /// means the digest length, as required by RFC 7518.
+    ///
+    /// Other lengths are NOT standard, only set this to interoperate with
+    /// legacy systems that use them.
+    pub pss_salt_len: Option<u32>,
 }
 
+#[cfg(feature = "rsa")]
 impl RsaPrivateKey {
     /// bits >= 2048.
+    ///
+    /// The key material is the same for PKCS#1 v1.5 and PSS, `algorithm`
+    /// decides which padding is used for signing and which `alg` is published
+    /// in the JWK.
+    #[cfg(feature = "sign")]
     pub fn generate(bits: u32, algorithm: RsaAlgorithm) -> Result<Self> {
         if bits < 2048 {
             return Err(Error::UnsupportedOrInvalidKey);
@@ -90,6 +121,7 @@ impl RsaPrivateKey {
             private_key: PKey::from_rsa(Rsa::generate(bits)?)?,
             algorithm,
             verify_any: false,
+            pss_salt_len: None,
         })
     }
 
@@ -101,9 +133,11 @@ impl RsaPrivateKey {
             private_key: pkey,
             algorithm,
             verify_any: false,
+            pss_salt_len: None,
         })
     }
 
+    #[cfg(feature = "sign")]
     pub(crate) fn from_pkey_without_check(
         pkey: PKey<Private>,
         algorithm: RsaAlgorithm,
@@ -115,6 +149,7 @@ impl RsaPrivateKey {
             private_key: pkey,
             algorithm,
             verify_any: false,
+            pss_salt_len: None,
         })
     }
 
@@ -123,6 +158,7 @@ impl RsaPrivateKey {
         Self::from_pkey(pk, algorithm)
     }
 
+    #[cfg(feature = "sign")]
     pub fn private_key_to_pem_pkcs8(&self) -> Result<String> {
         Ok(String::from_utf8(
             self.private_key.private_key_to_pem_pkcs8()?,
@@ -148,6 +184,7 @@ impl RsaPrivateKey {
     }
 }
 
+#[cfg(all(feature = "rsa", feature = "sign"))]
 impl PrivateKeyToJwk for RsaPrivateKey {
     #[allow(clippy::many_single_char_names)]
     fn private_key_to_jwk(&self) -> Result<Jwk> {
@@ -184,6 +221,7 @@ impl PrivateKeyToJwk for RsaPrivateKey {
     }
 }
 
+#[cfg(feature = "rsa")]
 impl PublicKeyToJwk for RsaPrivateKey {
     fn public_key_to_jwk(&self) -> Result<Jwk> {
         Ok(Jwk {
@@ -202,15 +240,19 @@ impl PublicKeyToJwk for RsaPrivateKey {
 }
 
 /// RSA Public Key.
-#[derive(Debug)]
+#[cfg(feature = "rsa")]
+#[derive(Debug, Clone)]
 pub struct RsaPublicKey {
     public_key: PKey<Public>,
     /// If this is `None`, this key verifies signatures generated by ANY RSA
     /// algorithms. Otherwise it ONLY verifies signatures generated by this
     /// algorithm.
     pub algorithm: Option<RsaAlgorithm>,
+    /// Non-standard PSS salt length, see `RsaPrivateKey::pss_salt_len`.
+    pub pss_salt_len: Option<u32>,
 }
 
+#[cfg(feature = "rsa")]
 impl RsaPublicKey {
     pub(crate) fn from_pkey(pkey: PKey<Public>, algorithm: Option<RsaAlgorithm>) -> Result<Self> {
         if pkey.id() != Id::RSA || pkey.bits() < 2048 {
@@ -219,6 +261,7 @@ impl RsaPublicKey {
         Ok(Self {
             public_key: pkey,
             algorithm,
+            pss_salt_len: None,
         })
     }
 
@@ -233,6 +276,8 @@ impl RsaPublicKey {
         }
     }
 
+    /// From big-endian `n` and `e`. Leading zero bytes are accepted, e.g. the
+    /// non-minimal `e` (`00 01 00 01`) some libraries produce.
     pub fn from_components(n: &[u8], e: &[u8], algorithm: Option<RsaAlgorithm>) -> Result<Self> {
         let rsa = Rsa::from_public_components(BigNum::from_slice(n)?, BigNum::from_slice(e)?)?;
         Self::from_pkey(PKey::from_rsa(rsa)?, algorithm)
@@ -250,6 +295,12 @@ impl RsaPublicKey {
         )?)
     }
 
+    /// Size of the modulus in bits.
+    #[inline]
+    pub fn bits(&self) -> u32 {
+        self.public_key.bits()
+    }
+
     pub fn n(&self) -> Result<Vec<u8>> {
         Ok(self.public_key.rsa()?.n().to_vec())
     }
@@ -259,6 +310,27 @@ impl RsaPublicKey {
     }
 }
 
+/// Compares the key material, `algorithm` is ignored.
+#[cfg(feature = "rsa")]
+impl PartialEq for RsaPublicKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.public_key.public_eq(&other.public_key)
+    }
+}
+
+#[cfg(feature = "rsa")]
+impl Eq for RsaPublicKey {}
+
+/// Hashes `n` and `e` like `PartialEq` compares them, `algorithm` is ignored.
+#[cfg(feature = "rsa")]
+impl std::hash::Hash for RsaPublicKey {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.n().ok().hash(state);
+        self.e().ok().hash(state);
+    }
+}
+
+#[cfg(feature = "rsa")]
 impl PublicKeyToJwk for RsaPublicKey {
     fn public_key_to_jwk(&self) -> Result<Jwk> {
         Ok(Jwk {
@@ -272,12 +344,23 @@ impl PublicKeyToJwk for RsaPublicKey {
     }
 }
 
+#[cfg(feature = "rsa")]
+fn pss_saltlen(len: Option<u32>) -> Result<RsaPssSaltlen> {
+    match len {
+        None => Ok(RsaPssSaltlen::DIGEST_LENGTH),
+        Some(len) => Ok(RsaPssSaltlen::custom(
+            i32::try_from(len).map_err(|_| Error::UnsupportedOrInvalidKey)?,
+        )),
+    }
+}
+
+#[cfg(all(feature = "rsa", feature = "sign"))]
 impl SigningKey for RsaPrivateKey {
     fn sign(&self, v: &[u8]) -> Result<SmallVec<[u8; 64]>> {
         let mut signer = Signer::new(self.algorithm.digest(), self.private_key.as_ref())?;
         if self.algorithm.is_pss() {
             signer.set_rsa_padding(Padding::PKCS1_PSS)?;
-            signer.set_rsa_pss_saltlen(RsaPssSaltlen::DIGEST_LENGTH)?;
+            signer.set_rsa_pss_saltlen(pss_saltlen(self.pss_salt_len)?)?;
         }
 
         signer.update(v)?;
@@ -289,6 +372,7 @@ impl SigningKey for RsaPrivateKey {
     }
 }
 
+#[cfg(feature = "rsa")]
 impl VerificationKey for RsaPrivateKey {
     fn verify(&self, v: &[u8], sig: &[u8], alg: &str) -> Result<()> {
         let alg = if self.verify_any {
@@ -303,7 +387,7 @@ impl VerificationKey for RsaPrivateKey {
         let mut verifier = Verifier::new(alg.digest(), self.private_key.as_ref())?;
         if alg.is_pss() {
             verifier.set_rsa_padding(Padding::PKCS1_PSS)?;
-            verifier.set_rsa_pss_saltlen(RsaPssSaltlen::DIGEST_LENGTH)?;
+            verifier.set_rsa_pss_saltlen(pss_saltlen(self.pss_salt_len)?)?;
         }
         if verifier.verify_oneshot(sig, v)? {
             Ok(())
@@ -313,6 +397,7 @@ impl VerificationKey for RsaPrivateKey {
     }
 }
 
+#[cfg(feature = "rsa")]
 impl VerificationKey for RsaPublicKey {
     fn verify(&self, v: &[u8], sig: &[u8], alg: &str) -> Result<()> {
         let alg = if let Some(self_alg) = self.algorithm {
@@ -327,7 +412,7 @@ impl VerificationKey for RsaPublicKey {
         let mut verifier = Verifier::new(alg.digest(), self.public_key.as_ref())?;
         if alg.is_pss() {
             verifier.set_rsa_padding(Padding::PKCS1_PSS)?;
-            verifier.set_rsa_pss_saltlen(RsaPssSaltlen::DIGEST_LENGTH)?;
+            verifier.set_rsa_pss_saltlen(pss_saltlen(self.pss_salt_len)?)?;
         }
         if verifier.verify_oneshot(sig, v)? {
             Ok(())
@@ -337,11 +422,13 @@ impl VerificationKey for RsaPublicKey {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "sign", feature = "rsa", feature = "ecdsa"))]
 mod tests {
+    use serde_json::{Map, Value};
+
     use crate::{
         ecdsa::{EcdsaAlgorithm, EcdsaPrivateKey},
-        SomePrivateKey,
+        sign, verify, HeaderAndClaims, SomePrivateKey,
     };
 
     use super::*;
@@ -387,6 +474,22 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn private_jwk_round_trip() -> Result<()> {
+        let k = RsaPrivateKey::generate(2048, RsaAlgorithm::RS384)?;
+        let jwk = k.private_key_to_jwk()?;
+        for v in [&jwk.d, &jwk.p, &jwk.q, &jwk.dp, &jwk.dq, &jwk.qi] {
+            assert!(v.is_some());
+        }
+        assert!(k.public_key_to_jwk()?.d.is_none());
+
+        let k1 = jwk.to_signing_key(RsaAlgorithm::RS256)?;
+        assert_eq!(k1.alg(), "RS384");
+        let jwk1 = k1.private_key_to_jwk()?;
+        assert_eq!(serde_json::to_value(&jwk)?, serde_json::to_value(&jwk1)?);
+        Ok(())
+    }
+
     #[test]
     fn test_private_key_from_jwk_n_e_d_only() -> Result<()> {
         let k = RsaPrivateKey::generate(2048, RsaAlgorithm::PS256)?;
@@ -405,16 +508,55 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn non_minimal_e() -> Result<()> {
+        let k = RsaPrivateKey::generate(2048, RsaAlgorithm::RS256)?;
+        assert_eq!(k.e()?, [1, 0, 1]);
+        let pk = RsaPublicKey::from_components(&k.n()?, &[0, 1, 0, 1], None)?;
+        assert_eq!(pk, RsaPublicKey::from_components(&k.n()?, &k.e()?, None)?);
+
+        let mut jwk = k.public_key_to_jwk()?;
+        jwk.e = Some("AAEAAQ".into());
+        let token = sign(&mut HeaderAndClaims::new_dynamic(), &k)?;
+        verify::<Map<String, Value>>(&token, &jwk.to_verification_key()?)?;
+        verify::<Map<String, Value>>(&token, &pk)?;
+        Ok(())
+    }
+
+    #[test]
+    fn generate_pss() -> Result<()> {
+        let k = RsaPrivateKey::generate(2048, RsaAlgorithm::PS256)?;
+        assert_eq!(k.alg(), "PS256");
+        assert_eq!(k.public_key_to_jwk()?.alg.as_deref(), Some("PS256"));
+
+        let sig = k.sign(b"...")?;
+        let pk = RsaPublicKey::from_components(&k.n()?, &k.e()?, Some(RsaAlgorithm::PS256))?;
+        pk.verify(b"...", &sig, "PS256")?;
+
+        let rs256 = RsaPublicKey::from_components(&k.n()?, &k.e()?, Some(RsaAlgorithm::RS256))?;
+        assert!(rs256.verify(b"...", &sig, "PS256").is_err());
+        assert!(rs256.verify(b"...", &sig, "RS256").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn pss_custom_salt_len() -> Result<()> {
+        let mut k = RsaPrivateKey::generate(2048, RsaAlgorithm::PS256)?;
+        k.pss_salt_len = Some(20);
+        let sig = k.sign(b"...")?;
+        k.verify(b"...", &sig, "PS256")?;
+
+        let mut pk = RsaPublicKey::from_components(&k.n()?, &k.e()?, None)?;
+        assert!(pk.verify(b"...", &sig, "PS256").is_err());
+        pk.pss_salt_len = Some(20);
+        pk.verify(b"...", &sig, "PS256")?;
+        assert!(pk.verify(b"....", &sig, "PS256").is_err());
+        Ok(())
+    }
+
     #[test]
     fn sign_verify() -> Result<()> {
-        for alg in [
-            RsaAlgorithm::RS256,
-            RsaAlgorithm::RS384,
-            RsaAlgorithm::RS512,
-            RsaAlgorithm::PS256,
-            RsaAlgorithm::PS384,
-            RsaAlgorithm::PS512,
-        ] {
+        for &alg in RsaAlgorithm::all() {
             let k = RsaPrivateKey::generate(2048, alg)?;
             let pk = RsaPublicKey::from_pem(k.public_key_to_pem()?.as_bytes(), None)?;
             let sig = k.sign(b"...")?;
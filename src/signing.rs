@@ -0,0 +1,98 @@
+//! Signing options.
+
+use crate::jwk::Jwk;
+
+/// Options for `sign_with_options`.
+///
+/// The default options sign exactly like `sign`.
+#[non_exhaustive]
+#[derive(Debug, Default, Clone)]
+pub struct SignOptions {
+    omit_iat: bool,
+    typ: Option<String>,
+    cty: Option<String>,
+    kid: Option<String>,
+    jwk: Option<Jwk>,
+    canonicalized: bool,
+}
+
+impl SignOptions {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Leave `iat` out of the token, even if it is set in the claims.
+    #[inline]
+    pub fn set_omit_iat(&mut self, omit: bool) -> &mut Self {
+        self.omit_iat = omit;
+        self
+    }
+
+    /// Set the `typ` header, e.g. `at+jwt`.
+    #[inline]
+    pub fn set_typ(&mut self, typ: impl Into<String>) -> &mut Self {
+        self.typ = Some(typ.into());
+        self
+    }
+
+    /// Set the `cty` header, e.g. `JWT` for nested tokens.
+    #[inline]
+    pub fn set_cty(&mut self, cty: impl Into<String>) -> &mut Self {
+        self.cty = Some(cty.into());
+        self
+    }
+
+    /// Set the `kid` header. Takes precedence over the id of the key.
+    #[inline]
+    pub fn set_kid(&mut self, kid: impl Into<String>) -> &mut Self {
+        self.kid = Some(kid.into());
+        self
+    }
+
+    /// Embed `jwk` in the `jwk` header, e.g. for DPoP proofs.
+    ///
+    /// This should be the PUBLIC key, i.e. from `PublicKeyToJwk`.
+    #[inline]
+    pub fn set_jwk(&mut self, jwk: Jwk) -> &mut Self {
+        self.jwk = Some(jwk);
+        self
+    }
+
+    /// Serialize the claims as canonicalized JSON, see `sign_canonicalized`.
+    #[inline]
+    pub fn set_canonicalized(&mut self, canonicalized: bool) -> &mut Self {
+        self.canonicalized = canonicalized;
+        self
+    }
+
+    #[inline]
+    pub(crate) fn omit_iat(&self) -> bool {
+        self.omit_iat
+    }
+
+    #[inline]
+    pub(crate) fn typ(&self) -> Option<&str> {
+        self.typ.as_deref()
+    }
+
+    #[inline]
+    pub(crate) fn cty(&self) -> Option<&str> {
+        self.cty.as_deref()
+    }
+
+    #[inline]
+    pub(crate) fn kid(&self) -> Option<&str> {
+        self.kid.as_deref()
+    }
+
+    #[inline]
+    pub(crate) fn jwk(&self) -> Option<&Jwk> {
+        self.jwk.as_ref()
+    }
+
+    #[inline]
+    pub(crate) fn canonicalized(&self) -> bool {
+        self.canonicalized
+    }
+}
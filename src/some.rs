@@ -1,39 +1,63 @@
 //! Enum of HMAC / EC / RSA / Ed Keys.
+// With every asymmetric family disabled the enums are empty.
+#![cfg_attr(
+    not(any(feature = "ecdsa", feature = "eddsa", feature = "rsa")),
+    allow(unused_imports, unused_variables)
+)]
 
-use openssl::pkey::{Id, PKey};
-
-use crate::{
-    ecdsa::{EcdsaPrivateKey, EcdsaPublicKey},
-    eddsa::{Ed25519PrivateKey, Ed25519PublicKey},
-    jwk::Jwk,
-    rsa::{RsaAlgorithm, RsaPrivateKey, RsaPublicKey},
-    Error, PrivateKeyToJwk, PublicKeyToJwk, Result, SigningKey, VerificationKey,
+use openssl::{
+    pkey::{Id, PKey, Public},
+    x509::X509,
 };
 
+#[cfg(feature = "ecdsa")]
+use crate::ecdsa::{EcdsaAlgorithm, EcdsaPrivateKey, EcdsaPublicKey};
+#[cfg(feature = "eddsa")]
+use crate::eddsa::{Ed25519PrivateKey, Ed25519PublicKey};
+#[cfg(feature = "rsa")]
+use crate::rsa::{RsaPrivateKey, RsaPublicKey};
+use crate::{jwk::Jwk, rsa::RsaAlgorithm, Error, PublicKeyToJwk, Result, VerificationKey};
+#[cfg(feature = "sign")]
+use crate::{PrivateKeyToJwk, SigningKey};
+
 /// An RSA, EC or Ed25519 private key.
 ///
 /// Use this if you just want to load SOME private key from an external pem
 /// file.
+///
+/// Only variants of the enabled `ecdsa`, `eddsa` and `rsa` features are
+/// present.
 #[non_exhaustive]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum SomePrivateKey {
+    #[cfg(feature = "eddsa")]
     Ed25519(Ed25519PrivateKey),
+    #[cfg(feature = "ecdsa")]
     Ecdsa(EcdsaPrivateKey),
+    #[cfg(feature = "rsa")]
     Rsa(RsaPrivateKey),
 }
 
 /// An RSA, EC or Ed25519 public.
 ///
 /// Use this if you just want to load SOME public key from an external pem file
-/// or JWK.
+/// or JWK. Like `SomePrivateKey`, only variants of enabled features are
+/// present.
+///
+/// Two keys are equal if they have the same key material, and equal keys have
+/// the same hash, so they can be used in a `HashSet` or as `HashMap` keys.
 #[non_exhaustive]
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum SomePublicKey {
+    #[cfg(feature = "eddsa")]
     Ed25519(Ed25519PublicKey),
+    #[cfg(feature = "ecdsa")]
     Ecdsa(EcdsaPublicKey),
+    #[cfg(feature = "rsa")]
     Rsa(RsaPublicKey),
 }
 
+#[cfg(feature = "eddsa")]
 impl From<Ed25519PrivateKey> for SomePrivateKey {
     #[inline]
     fn from(k: Ed25519PrivateKey) -> SomePrivateKey {
@@ -41,6 +65,7 @@ impl From<Ed25519PrivateKey> for SomePrivateKey {
     }
 }
 
+#[cfg(feature = "ecdsa")]
 impl From<EcdsaPrivateKey> for SomePrivateKey {
     #[inline]
     fn from(k: EcdsaPrivateKey) -> SomePrivateKey {
@@ -48,6 +73,7 @@ impl From<EcdsaPrivateKey> for SomePrivateKey {
     }
 }
 
+#[cfg(feature = "rsa")]
 impl From<RsaPrivateKey> for SomePrivateKey {
     #[inline]
     fn from(k: RsaPrivateKey) -> SomePrivateKey {
@@ -55,6 +81,7 @@ impl From<RsaPrivateKey> for SomePrivateKey {
     }
 }
 
+#[cfg(feature = "eddsa")]
 impl From<Ed25519PublicKey> for SomePublicKey {
     #[inline]
     fn from(k: Ed25519PublicKey) -> SomePublicKey {
@@ -62,6 +89,7 @@ impl From<Ed25519PublicKey> for SomePublicKey {
     }
 }
 
+#[cfg(feature = "ecdsa")]
 impl From<EcdsaPublicKey> for SomePublicKey {
     #[inline]
     fn from(k: EcdsaPublicKey) -> SomePublicKey {
@@ -69,6 +97,7 @@ impl From<EcdsaPublicKey> for SomePublicKey {
     }
 }
 
+#[cfg(feature = "rsa")]
 impl From<RsaPublicKey> for SomePublicKey {
     #[inline]
     fn from(k: RsaPublicKey) -> SomePublicKey {
@@ -82,19 +111,24 @@ impl SomePrivateKey {
     /// For an EC/Ed25519 private key, algorithm is deduced from the curve, e.g.
     /// P-256 -> ES256.
     ///
-    /// For an RSA private key, `if_rsa_algorithm` is used.
+    /// For an RSA private key, `if_rsa_algorithm` is used. RSA keys are
+    /// rejected with `UnsupportedOrInvalidKey` if the `rsa` feature is off.
+    #[cfg_attr(not(feature = "rsa"), allow(unused_variables))]
     pub fn from_pem(pem: &[u8], if_rsa_algorithm: RsaAlgorithm) -> Result<Self> {
         let pk = PKey::private_key_from_pem(pem)?;
 
         match pk.id() {
+            #[cfg(feature = "rsa")]
             Id::RSA => {
                 let k = RsaPrivateKey::from_pkey(pk, if_rsa_algorithm)?;
                 Ok(Self::Rsa(k))
             }
+            #[cfg(feature = "ecdsa")]
             Id::EC => {
                 let k = EcdsaPrivateKey::from_pkey(pk)?;
                 Ok(Self::Ecdsa(k))
             }
+            #[cfg(feature = "eddsa")]
             Id::ED25519 => {
                 let k = Ed25519PrivateKey::from_pkey(pk)?;
                 Ok(Self::Ed25519(k))
@@ -103,39 +137,53 @@ impl SomePrivateKey {
         }
     }
 
+    #[cfg(feature = "sign")]
     pub fn private_key_to_pem_pkcs8(&self) -> Result<String> {
-        match self {
-            SomePrivateKey::Ed25519(ed) => ed.private_key_to_pem_pkcs8(),
-            SomePrivateKey::Ecdsa(ec) => ec.private_key_to_pem_pkcs8(),
-            SomePrivateKey::Rsa(rsa) => rsa.private_key_to_pem_pkcs8(),
+        match *self {
+            #[cfg(feature = "eddsa")]
+            SomePrivateKey::Ed25519(ref ed) => ed.private_key_to_pem_pkcs8(),
+            #[cfg(feature = "ecdsa")]
+            SomePrivateKey::Ecdsa(ref ec) => ec.private_key_to_pem_pkcs8(),
+            #[cfg(feature = "rsa")]
+            SomePrivateKey::Rsa(ref rsa) => rsa.private_key_to_pem_pkcs8(),
         }
     }
 
     pub fn public_key_to_pem(&self) -> Result<String> {
-        match self {
-            SomePrivateKey::Ed25519(ed) => ed.public_key_to_pem(),
-            SomePrivateKey::Ecdsa(ec) => ec.public_key_to_pem(),
-            SomePrivateKey::Rsa(rsa) => rsa.public_key_to_pem(),
+        match *self {
+            #[cfg(feature = "eddsa")]
+            SomePrivateKey::Ed25519(ref ed) => ed.public_key_to_pem(),
+            #[cfg(feature = "ecdsa")]
+            SomePrivateKey::Ecdsa(ref ec) => ec.public_key_to_pem(),
+            #[cfg(feature = "rsa")]
+            SomePrivateKey::Rsa(ref rsa) => rsa.public_key_to_pem(),
         }
     }
 }
 
 impl PublicKeyToJwk for SomePrivateKey {
     fn public_key_to_jwk(&self) -> Result<Jwk> {
-        match self {
-            SomePrivateKey::Ed25519(ed) => ed.public_key_to_jwk(),
-            SomePrivateKey::Ecdsa(ec) => ec.public_key_to_jwk(),
-            SomePrivateKey::Rsa(rsa) => rsa.public_key_to_jwk(),
+        match *self {
+            #[cfg(feature = "eddsa")]
+            SomePrivateKey::Ed25519(ref ed) => ed.public_key_to_jwk(),
+            #[cfg(feature = "ecdsa")]
+            SomePrivateKey::Ecdsa(ref ec) => ec.public_key_to_jwk(),
+            #[cfg(feature = "rsa")]
+            SomePrivateKey::Rsa(ref rsa) => rsa.public_key_to_jwk(),
         }
     }
 }
 
+#[cfg(feature = "sign")]
 impl PrivateKeyToJwk for SomePrivateKey {
     fn private_key_to_jwk(&self) -> Result<Jwk> {
-        match self {
-            SomePrivateKey::Ed25519(ed) => ed.private_key_to_jwk(),
-            SomePrivateKey::Ecdsa(ec) => ec.private_key_to_jwk(),
-            SomePrivateKey::Rsa(rsa) => rsa.private_key_to_jwk(),
+        match *self {
+            #[cfg(feature = "eddsa")]
+            SomePrivateKey::Ed25519(ref ed) => ed.private_key_to_jwk(),
+            #[cfg(feature = "ecdsa")]
+            SomePrivateKey::Ecdsa(ref ec) => ec.private_key_to_jwk(),
+            #[cfg(feature = "rsa")]
+            SomePrivateKey::Rsa(ref rsa) => rsa.private_key_to_jwk(),
         }
     }
 }
@@ -149,16 +197,65 @@ impl SomePublicKey {
     /// For an RSA public key, signatures generated by any RSA algorithms can be
     /// verified.
     pub fn from_pem(pem: &[u8]) -> Result<Self> {
-        let pk = PKey::public_key_from_pem(pem)?;
+        Self::from_pkey(PKey::public_key_from_pem(pem)?)
+    }
+
+    /// Read every `BEGIN PUBLIC KEY` (or other) PEM block of a bundle of
+    /// concatenated PEM public keys, like `from_pem`.
+    ///
+    /// Blocks that fail to parse are reported as errors in place, so callers
+    /// can decide whether to skip them.
+    pub fn from_pem_bundle(pem: &[u8]) -> Vec<Result<Self>> {
+        let pem = match String::from_utf8(pem.to_vec()) {
+            Ok(pem) => pem,
+            Err(e) => return vec![Err(e.into())],
+        };
+        let mut keys = Vec::new();
+        let mut block: Option<String> = None;
+        for line in pem.lines().map(str::trim) {
+            if line.starts_with("-----BEGIN ") {
+                block = Some(String::new());
+            }
+            if let Some(ref mut b) = block {
+                b.push_str(line);
+                b.push('\n');
+            }
+            if line.starts_with("-----END ") {
+                if let Some(b) = block.take() {
+                    keys.push(Self::from_pem(b.as_bytes()));
+                }
+            }
+        }
+        keys
+    }
+
+    /// Read the public key of an X.509 certificate in PEM (`BEGIN
+    /// CERTIFICATE`).
+    ///
+    /// The certificate itself is NOT validated, e.g. its validity period and
+    /// signature are not checked. The key is handled like in `from_pem`.
+    pub fn from_certificate_pem(pem: &[u8]) -> Result<Self> {
+        Self::from_pkey(X509::from_pem(pem)?.public_key()?)
+    }
+
+    /// Like `from_certificate_pem`, but for a DER encoded certificate.
+    pub fn from_certificate_der(der: &[u8]) -> Result<Self> {
+        Self::from_pkey(X509::from_der(der)?.public_key()?)
+    }
+
+    pub(crate) fn from_pkey(pk: PKey<Public>) -> Result<Self> {
         match pk.id() {
+            #[cfg(feature = "rsa")]
             Id::RSA => {
                 let k = RsaPublicKey::from_pkey(pk, None)?;
                 Ok(Self::Rsa(k))
             }
+            #[cfg(feature = "ecdsa")]
             Id::EC => {
                 let k = EcdsaPublicKey::from_pkey(pk)?;
                 Ok(Self::Ecdsa(k))
             }
+            #[cfg(feature = "eddsa")]
             Id::ED25519 => {
                 let k = Ed25519PublicKey::from_pkey(pk)?;
                 Ok(Self::Ed25519(k))
@@ -168,58 +265,276 @@ impl SomePublicKey {
     }
 
     pub fn to_pem(&self) -> Result<String> {
-        match self {
-            SomePublicKey::Ed25519(ed) => ed.to_pem(),
-            SomePublicKey::Ecdsa(ec) => ec.to_pem(),
-            SomePublicKey::Rsa(rsa) => rsa.to_pem(),
+        match *self {
+            #[cfg(feature = "eddsa")]
+            SomePublicKey::Ed25519(ref ed) => ed.to_pem(),
+            #[cfg(feature = "ecdsa")]
+            SomePublicKey::Ecdsa(ref ec) => ec.to_pem(),
+            #[cfg(feature = "rsa")]
+            SomePublicKey::Rsa(ref rsa) => rsa.to_pem(),
+        }
+    }
+
+    /// Whether `alg` is an algorithm of this key's family, e.g. `ES256` or
+    /// `BP256R1` for an EC key. `HS*` is never accepted for a public key.
+    pub(crate) fn accepts_alg(&self, alg: &str) -> bool {
+        match *self {
+            #[cfg(feature = "eddsa")]
+            SomePublicKey::Ed25519(_) => alg == "EdDSA",
+            #[cfg(feature = "ecdsa")]
+            SomePublicKey::Ecdsa(_) => EcdsaAlgorithm::from_name(alg).is_ok(),
+            #[cfg(feature = "rsa")]
+            SomePublicKey::Rsa(_) => RsaAlgorithm::from_name(alg).is_ok(),
         }
     }
 }
 
+#[cfg(feature = "sign")]
 impl SigningKey for SomePrivateKey {
     fn alg(&self) -> &'static str {
-        match self {
-            SomePrivateKey::Ed25519(ed) => ed.alg(),
-            SomePrivateKey::Ecdsa(ec) => ec.alg(),
-            SomePrivateKey::Rsa(rsa) => rsa.alg(),
+        match *self {
+            #[cfg(feature = "eddsa")]
+            SomePrivateKey::Ed25519(ref ed) => ed.alg(),
+            #[cfg(feature = "ecdsa")]
+            SomePrivateKey::Ecdsa(ref ec) => ec.alg(),
+            #[cfg(feature = "rsa")]
+            SomePrivateKey::Rsa(ref rsa) => rsa.alg(),
         }
     }
 
     fn sign(&self, v: &[u8]) -> crate::Result<smallvec::SmallVec<[u8; 64]>> {
-        match self {
-            SomePrivateKey::Ed25519(ed) => ed.sign(v),
-            SomePrivateKey::Ecdsa(ec) => ec.sign(v),
-            SomePrivateKey::Rsa(rsa) => rsa.sign(v),
+        match *self {
+            #[cfg(feature = "eddsa")]
+            SomePrivateKey::Ed25519(ref ed) => ed.sign(v),
+            #[cfg(feature = "ecdsa")]
+            SomePrivateKey::Ecdsa(ref ec) => ec.sign(v),
+            #[cfg(feature = "rsa")]
+            SomePrivateKey::Rsa(ref rsa) => rsa.sign(v),
         }
     }
 }
 
 impl VerificationKey for SomePrivateKey {
     fn verify(&self, v: &[u8], sig: &[u8], alg: &str) -> crate::Result<()> {
-        match self {
-            SomePrivateKey::Ed25519(ed) => ed.verify(v, sig, alg),
-            SomePrivateKey::Ecdsa(ec) => ec.verify(v, sig, alg),
-            SomePrivateKey::Rsa(rsa) => rsa.verify(v, sig, alg),
+        match *self {
+            #[cfg(feature = "eddsa")]
+            SomePrivateKey::Ed25519(ref ed) => ed.verify(v, sig, alg),
+            #[cfg(feature = "ecdsa")]
+            SomePrivateKey::Ecdsa(ref ec) => ec.verify(v, sig, alg),
+            #[cfg(feature = "rsa")]
+            SomePrivateKey::Rsa(ref rsa) => rsa.verify(v, sig, alg),
         }
     }
 }
 
 impl VerificationKey for SomePublicKey {
     fn verify(&self, v: &[u8], sig: &[u8], alg: &str) -> crate::Result<()> {
-        match self {
-            SomePublicKey::Ed25519(ed) => ed.verify(v, sig, alg),
-            SomePublicKey::Ecdsa(ec) => ec.verify(v, sig, alg),
-            SomePublicKey::Rsa(rsa) => rsa.verify(v, sig, alg),
+        match *self {
+            #[cfg(feature = "eddsa")]
+            SomePublicKey::Ed25519(ref ed) => ed.verify(v, sig, alg),
+            #[cfg(feature = "ecdsa")]
+            SomePublicKey::Ecdsa(ref ec) => ec.verify(v, sig, alg),
+            #[cfg(feature = "rsa")]
+            SomePublicKey::Rsa(ref rsa) => rsa.verify(v, sig, alg),
         }
     }
 }
 
 impl PublicKeyToJwk for SomePublicKey {
     fn public_key_to_jwk(&self) -> Result<Jwk> {
-        match self {
-            SomePublicKey::Ed25519(ed) => ed.public_key_to_jwk(),
-            SomePublicKey::Ecdsa(ec) => ec.public_key_to_jwk(),
-            SomePublicKey::Rsa(rsa) => rsa.public_key_to_jwk(),
+        match *self {
+            #[cfg(feature = "eddsa")]
+            SomePublicKey::Ed25519(ref ed) => ed.public_key_to_jwk(),
+            #[cfg(feature = "ecdsa")]
+            SomePublicKey::Ecdsa(ref ec) => ec.public_key_to_jwk(),
+            #[cfg(feature = "rsa")]
+            SomePublicKey::Rsa(ref rsa) => rsa.public_key_to_jwk(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "sign"))]
+mod tests {
+    #[cfg(feature = "ecdsa")]
+    use crate::ecdsa::EcdsaAlgorithm;
+    use crate::{rsa::RsaAlgorithm, sign, verify, HeaderAndClaims};
+    #[cfg(feature = "rsa")]
+    use openssl::{
+        asn1::Asn1Time,
+        hash::MessageDigest,
+        x509::{X509Builder, X509NameBuilder},
+    };
+    use serde_json::{Map, Value};
+
+    use super::*;
+
+    #[cfg(feature = "rsa")]
+    #[test]
+    fn from_certificate() -> Result<()> {
+        let k = RsaPrivateKey::generate(2048, RsaAlgorithm::RS256)?;
+        let pkey = PKey::private_key_from_pem(k.private_key_to_pem_pkcs8()?.as_bytes())?;
+
+        let mut name = X509NameBuilder::new()?;
+        name.append_entry_by_text("CN", "issuer")?;
+        let name = name.build();
+        let mut cert = X509Builder::new()?;
+        cert.set_version(2)?;
+        cert.set_subject_name(&name)?;
+        cert.set_issuer_name(&name)?;
+        cert.set_pubkey(&pkey)?;
+        cert.set_not_before(Asn1Time::days_from_now(0)?.as_ref())?;
+        cert.set_not_after(Asn1Time::days_from_now(1)?.as_ref())?;
+        cert.sign(&pkey, MessageDigest::sha256())?;
+        let cert = cert.build();
+
+        let token = sign(&mut HeaderAndClaims::new_dynamic(), &k)?;
+        for pk in [
+            SomePublicKey::from_certificate_pem(&cert.to_pem()?)?,
+            SomePublicKey::from_certificate_der(&cert.to_der()?)?,
+        ] {
+            assert!(matches!(pk, SomePublicKey::Rsa(_)));
+            verify::<Map<String, Value>>(&token, &pk)?;
+        }
+
+        // A bare public key is not a certificate.
+        assert!(SomePublicKey::from_certificate_pem(k.public_key_to_pem()?.as_bytes()).is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "ecdsa")]
+    #[test]
+    fn hash_by_key_material() -> Result<()> {
+        use std::collections::HashSet;
+
+        let ec = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let from_pem = SomePublicKey::from_pem(ec.public_key_to_pem()?.as_bytes())?;
+        let jwk = ec.public_key_to_jwk()?;
+        let from_coordinates = Jwk {
+            kty: "EC".into(),
+            crv: jwk.crv,
+            x: jwk.x,
+            y: jwk.y,
+            ..Default::default()
+        }
+        .to_verification_key()?;
+        assert_eq!(from_pem, from_coordinates);
+
+        let mut set = HashSet::new();
+        set.insert(from_pem);
+        set.insert(from_coordinates);
+        assert_eq!(set.len(), 1);
+
+        // The same point, compressed and uncompressed.
+        let compressed = SomePublicKey::from_pem(
+            b"-----BEGIN PUBLIC KEY-----
+MDkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDIgADtp5KIg51Y4x6m8ucENVfsCdfQNSF
+pmEJVbn3e5h8cFo=
+-----END PUBLIC KEY-----",
+        )?;
+        let uncompressed = SomePublicKey::from_pem(
+            b"-----BEGIN PUBLIC KEY-----
+MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEtp5KIg51Y4x6m8ucENVfsCdfQNSF
+pmEJVbn3e5h8cFqhKzwmIjeox15hRRQHowAwYV77hfNFDqGMaSUKIPVFSw==
+-----END PUBLIC KEY-----",
+        )?;
+        assert_eq!(compressed, uncompressed);
+        let mut points = HashSet::new();
+        points.insert(compressed);
+        points.insert(uncompressed);
+        assert_eq!(points.len(), 1);
+
+        #[cfg(feature = "rsa")]
+        {
+            let rsa = RsaPrivateKey::generate(2048, RsaAlgorithm::RS256)?;
+            let rsa_any = SomePublicKey::from_pem(rsa.public_key_to_pem()?.as_bytes())?;
+            let rsa_rs256 = rsa.public_key_to_jwk()?.to_verification_key()?;
+            assert_eq!(rsa_any, rsa_rs256);
+            set.insert(rsa_any);
+            set.insert(rsa_rs256);
+            assert_eq!(set.len(), 2);
         }
+        Ok(())
+    }
+
+    #[cfg(any(feature = "ecdsa", feature = "eddsa", feature = "rsa"))]
+    #[allow(clippy::vec_init_then_push)]
+    #[test]
+    fn from_concrete_keys() -> Result<()> {
+        let mut keys: Vec<SomePrivateKey> = Vec::new();
+        #[cfg(feature = "eddsa")]
+        keys.push(Ed25519PrivateKey::generate()?.into());
+        #[cfg(feature = "ecdsa")]
+        keys.push(EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?.into());
+        #[cfg(feature = "rsa")]
+        keys.push(RsaPrivateKey::generate(2048, RsaAlgorithm::PS256)?.into());
+        for k in &keys {
+            let pk: SomePublicKey = match *k {
+                #[cfg(feature = "eddsa")]
+                SomePrivateKey::Ed25519(ref k) => {
+                    Ed25519PublicKey::from_pem(k.public_key_to_pem()?.as_bytes())?.into()
+                }
+                #[cfg(feature = "ecdsa")]
+                SomePrivateKey::Ecdsa(ref k) => {
+                    EcdsaPublicKey::from_pem(k.public_key_to_pem()?.as_bytes())?.into()
+                }
+                #[cfg(feature = "rsa")]
+                SomePrivateKey::Rsa(ref k) => {
+                    RsaPublicKey::from_pem(k.public_key_to_pem()?.as_bytes(), None)?.into()
+                }
+            };
+            let token = sign(&mut HeaderAndClaims::new_dynamic(), k)?;
+            verify::<Map<String, Value>>(&token, &pk)?;
+        }
+        Ok(())
+    }
+
+    // Keys of a disabled family are rejected by `from_pem`.
+    #[cfg(not(all(feature = "ecdsa", feature = "eddsa", feature = "rsa")))]
+    fn assert_unsupported(k: PKey<openssl::pkey::Private>) -> Result<()> {
+        assert!(matches!(
+            SomePrivateKey::from_pem(&k.private_key_to_pem_pkcs8()?, RsaAlgorithm::RS256),
+            Err(Error::UnsupportedOrInvalidKey)
+        ));
+        assert!(matches!(
+            SomePublicKey::from_pem(&k.public_key_to_pem()?),
+            Err(Error::UnsupportedOrInvalidKey)
+        ));
+        Ok(())
+    }
+
+    #[cfg(not(feature = "ecdsa"))]
+    #[test]
+    fn ecdsa_disabled() -> Result<()> {
+        use openssl::{
+            ec::{EcGroup, EcKey},
+            nid::Nid,
+        };
+
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+        assert_unsupported(PKey::from_ec_key(EcKey::generate(&group)?)?)?;
+        let jwk = Jwk {
+            kty: "EC".into(),
+            crv: Some("P-256".into()),
+            x: Some("AA".into()),
+            y: Some("AA".into()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            jwk.to_verification_key(),
+            Err(Error::UnsupportedOrInvalidKey)
+        ));
+        Ok(())
+    }
+
+    #[cfg(not(feature = "eddsa"))]
+    #[test]
+    fn eddsa_disabled() -> Result<()> {
+        assert_unsupported(PKey::generate_ed25519()?)
+    }
+
+    #[cfg(not(feature = "rsa"))]
+    #[test]
+    fn rsa_disabled() -> Result<()> {
+        assert_unsupported(PKey::from_rsa(openssl::rsa::Rsa::generate(2048)?)?)
     }
 }
@@ -0,0 +1,163 @@
+//! Helpers for writing tests against code that verifies tokens.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::{
+    jwk::{JwkSet, JwksProvider},
+    sign, Error, HeaderAndClaims, Result, SigningKey,
+};
+
+/// Mint a signed token with common claims: `iss`, `aud`, `iat` set to now,
+/// and `exp` set to `ttl` from now. `kid` is set in the header if given.
+///
+/// Only available with the `test-util` feature. Not meant for production
+/// use.
+pub fn mint_test_token(
+    k: &dyn SigningKey,
+    kid: Option<&str>,
+    iss: &str,
+    aud: &str,
+    ttl: Duration,
+) -> Result<String> {
+    let mut claims = HeaderAndClaims::new_dynamic();
+    claims
+        .set_iss(iss)
+        .add_aud(aud)
+        .set_iat_now()
+        .set_exp_from_now(ttl);
+    if let Some(kid) = kid {
+        claims.set_kid(kid);
+    }
+    sign(&mut claims, k)
+}
+
+/// An in-memory `JwksProvider` that always returns the same JWK Set, e.g. pass
+/// `|| provider.get_keys()` to `jwk::verify_with_jwks_fn`.
+///
+/// It counts `get_keys` calls, and can be made to fail on demand to test
+/// retry handling.
+///
+/// Only available with the `test-util` feature. Not meant for production
+/// use.
+#[derive(Debug)]
+pub struct StaticJwksProvider {
+    jwks: Arc<JwkSet>,
+    calls: AtomicUsize,
+    failures: AtomicUsize,
+}
+
+impl StaticJwksProvider {
+    pub fn new(jwks: JwkSet) -> Self {
+        Self {
+            jwks: Arc::new(jwks),
+            calls: AtomicUsize::new(0),
+            failures: AtomicUsize::new(0),
+        }
+    }
+
+    /// Make the next `n` `get_keys` calls fail.
+    pub fn fail_next(&self, n: usize) {
+        self.failures.store(n, Ordering::SeqCst);
+    }
+
+    /// Number of `get_keys` calls so far, including failed ones.
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+}
+
+impl JwksProvider for StaticJwksProvider {
+    /// Return the key set, or fail with `Error::IoError` if a failure was
+    /// injected with `fail_next`.
+    fn get_keys(
+        &self,
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Arc<JwkSet>>> + Send + '_>> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        let failing = self
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        let res = if failing {
+            Err(Error::IoError(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "injected failure",
+            )))
+        } else {
+            Ok(self.jwks.clone())
+        };
+        Box::pin(async move { res })
+    }
+}
+
+#[cfg(all(test, feature = "ecdsa"))]
+mod tests {
+    use serde_json::{Map, Value};
+
+    use crate::{
+        ecdsa::{EcdsaAlgorithm, EcdsaPrivateKey},
+        verify_with_options, VerifyOptions,
+    };
+
+    use super::*;
+
+    #[test]
+    fn mint_and_verify() -> Result<()> {
+        let k = EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?;
+        let token = mint_test_token(
+            &k,
+            Some("my-key"),
+            "https://issuer",
+            "my-api",
+            Duration::from_secs(60),
+        )?;
+
+        let mut options = VerifyOptions::new();
+        options.add_iss("https://issuer").add_aud("my-api");
+        let verified = verify_with_options::<Map<String, Value>>(&token, &k, &options)?;
+        let hc = verified.header_and_claims();
+        assert_eq!(hc.header().kid.as_deref(), Some("my-key"));
+        assert!(hc.claims().iat.is_some());
+        assert!(hc.claims().exp > hc.claims().iat);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn static_jwks_provider() -> Result<()> {
+        use crate::jwk::{verify_with_jwks_fn, WithKid};
+        use crate::PublicKeyToJwk;
+
+        let k = WithKid::new(
+            "k".into(),
+            EcdsaPrivateKey::generate(EcdsaAlgorithm::ES256)?,
+        );
+        let provider = StaticJwksProvider::new(JwkSet {
+            keys: vec![k.public_key_to_jwk()?],
+        });
+        let token = mint_test_token(&k, None, "iss", "aud", Duration::from_secs(60))?;
+        let mut options = VerifyOptions::new();
+        options.add_iss("iss").add_aud("aud");
+
+        provider.fail_next(2);
+        for _ in 0..2 {
+            assert!(matches!(
+                verify_with_jwks_fn::<Map<String, Value>, _, _>(
+                    &token,
+                    || provider.get_keys(),
+                    &options
+                )
+                .await,
+                Err(Error::IoError(_))
+            ));
+        }
+        verify_with_jwks_fn::<Map<String, Value>, _, _>(&token, || provider.get_keys(), &options)
+            .await?;
+        assert_eq!(provider.calls(), 3);
+        Ok(())
+    }
+}
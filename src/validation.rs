@@ -0,0 +1,630 @@
+//! Claims validation options.
+
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use serde_json::{Map, Value};
+
+use crate::{jwk::KeySource, Claims, Error, HeaderAndClaims, KeyPolicy, OneOrMany, Result};
+
+type ValidatorFn = dyn Fn(&Claims<Map<String, Value>>) -> Result<()> + Send + Sync;
+
+/// A custom claims validator, see `VerifyOptions::add_validator`.
+#[derive(Clone)]
+struct ClaimsValidator(Arc<ValidatorFn>);
+
+impl fmt::Debug for ClaimsValidator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ClaimsValidator")
+    }
+}
+
+/// Revoked token ids (`jti`), see `VerifyOptions::set_revocation`.
+///
+/// Storage is up to the implementation, e.g. an in-memory set or a cache
+/// synced from a database.
+pub trait Revocation: Send + Sync {
+    fn is_revoked(&self, jti: &str) -> bool;
+}
+
+impl Revocation for HashSet<String> {
+    fn is_revoked(&self, jti: &str) -> bool {
+        self.contains(jti)
+    }
+}
+
+#[derive(Clone)]
+struct RevocationRef(Arc<dyn Revocation>);
+
+impl fmt::Debug for RevocationRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Revocation")
+    }
+}
+
+/// Options for `verify_with_options`.
+///
+/// `exp`, `nbf` and `iat` are always checked. Other checks are only performed
+/// when configured.
+#[non_exhaustive]
+#[derive(Debug, Default, Clone)]
+pub struct VerifyOptions {
+    issuers: Vec<String>,
+    normalize_issuer_trailing_slash: bool,
+    auds: Vec<String>,
+    lenient_alg_case: bool,
+    strict_header: bool,
+    cty: Option<String>,
+    exp_leeway: Duration,
+    nbf_leeway: Duration,
+    proportional_leeway: Option<(u32, Duration)>,
+    max_nbf_future: Option<Duration>,
+    iat_leeway: Duration,
+    check_consistency: bool,
+    max_token_lifetime: Option<Duration>,
+    accept_der_ecdsa_signatures: bool,
+    detect_millisecond_dates: bool,
+    now_unix: Option<u64>,
+    key_policy: Option<KeyPolicy>,
+    revocation: Option<RevocationRef>,
+    validators: Vec<ClaimsValidator>,
+}
+
+impl VerifyOptions {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require that the token `iss` is one of `issuers`.
+    ///
+    /// Issuers are compared exactly, see also
+    /// `set_normalize_issuer_trailing_slash`.
+    #[inline]
+    pub fn set_issuers(&mut self, issuers: Vec<String>) -> &mut Self {
+        self.issuers = issuers;
+        self
+    }
+
+    #[inline]
+    pub fn add_iss(&mut self, iss: impl Into<String>) -> &mut Self {
+        self.issuers.push(iss.into());
+        self
+    }
+
+    /// Ignore a single trailing `/` when comparing issuers, i.e. treat
+    /// `https://issuer` and `https://issuer/` as equal.
+    ///
+    /// This relaxes the exact comparison required by OIDC. Off by default.
+    #[inline]
+    pub fn set_normalize_issuer_trailing_slash(&mut self, normalize: bool) -> &mut Self {
+        self.normalize_issuer_trailing_slash = normalize;
+        self
+    }
+
+    #[inline]
+    pub(crate) fn has_issuers(&self) -> bool {
+        !self.issuers.is_empty()
+    }
+
+    fn iss_matches(&self, expected: &str, iss: &str) -> bool {
+        if self.normalize_issuer_trailing_slash {
+            expected.strip_suffix('/').unwrap_or(expected) == iss.strip_suffix('/').unwrap_or(iss)
+        } else {
+            expected == iss
+        }
+    }
+
+    /// Require that the token `aud` contains at least one of `auds`.
+    ///
+    /// The matched audiences are reported in `Verified::matched_auds`.
+    #[inline]
+    pub fn set_auds(&mut self, auds: Vec<String>) -> &mut Self {
+        self.auds = auds;
+        self
+    }
+
+    #[inline]
+    pub fn add_aud(&mut self, aud: impl Into<String>) -> &mut Self {
+        self.auds.push(aud.into());
+        self
+    }
+
+    #[inline]
+    pub(crate) fn has_auds(&self) -> bool {
+        !self.auds.is_empty()
+    }
+
+    /// Accept tokens that expired (`exp`) at most `leeway` ago, to allow for
+    /// clock skew. Zero by default.
+    #[inline]
+    pub fn set_exp_leeway(&mut self, leeway: Duration) -> &mut Self {
+        self.exp_leeway = leeway;
+        self
+    }
+
+    /// Accept tokens that become valid (`nbf`) at most `leeway` in the
+    /// future, to allow for clock skew. Zero by default.
+    #[inline]
+    pub fn set_nbf_leeway(&mut self, leeway: Duration) -> &mut Self {
+        self.nbf_leeway = leeway;
+        self
+    }
+
+    /// Use `percent` percent of the token lifetime (`exp - iat`), but at
+    /// most `max`, as the `exp` and `nbf` leeway, so that short-lived tokens
+    /// get a proportionally small leeway.
+    ///
+    /// Replaces `set_exp_leeway` and `set_nbf_leeway` for tokens with both
+    /// `exp` and `iat`. Other tokens use those absolute leeways.
+    #[inline]
+    pub fn set_proportional_leeway(&mut self, percent: u32, max: Duration) -> &mut Self {
+        self.proportional_leeway = Some((percent, max));
+        self
+    }
+
+    /// The `exp` and `nbf` leeways for a token with these dates.
+    fn leeways(&self, iat: Option<SystemTime>, exp: Option<SystemTime>) -> (Duration, Duration) {
+        match (self.proportional_leeway, iat, exp) {
+            (Some((percent, max)), Some(iat), Some(exp)) => {
+                let lifetime = exp.duration_since(iat).unwrap_or_default();
+                let leeway = lifetime
+                    .checked_mul(percent)
+                    .map_or(max, |l| (l / 100).min(max));
+                (leeway, leeway)
+            }
+            _ => (self.exp_leeway, self.nbf_leeway),
+        }
+    }
+
+    /// Reject tokens that become valid (`nbf`) more than `max` in the future
+    /// with `Error::NbfTooFarInFuture`, as they are probably misissued.
+    ///
+    /// Tokens with `nbf` less than `max` in the future still fail with
+    /// `Error::Before` until `nbf`.
+    #[inline]
+    pub fn set_max_nbf_future(&mut self, max: Duration) -> &mut Self {
+        self.max_nbf_future = Some(max);
+        self
+    }
+
+    /// Accept tokens issued (`iat`) at most `leeway` in the future, to allow
+    /// for clock skew. Later ones fail with `Error::IssuedInFuture`. Zero by
+    /// default.
+    #[inline]
+    pub fn set_iat_leeway(&mut self, leeway: Duration) -> &mut Self {
+        self.iat_leeway = leeway;
+        self
+    }
+
+    /// Reject tokens with `iat` or `nbf` later than `exp` with
+    /// `Error::InconsistentClaims`, as they can never be valid.
+    ///
+    /// Off by default.
+    #[inline]
+    pub fn set_check_consistency(&mut self, check: bool) -> &mut Self {
+        self.check_consistency = check;
+        self
+    }
+
+    /// Reject tokens valid for longer than `lifetime`, i.e. with `exp - iat`
+    /// greater than `lifetime`, with `Error::LifetimeTooLong`. Tokens without
+    /// `exp` or `iat` are rejected too.
+    ///
+    /// Only the token's own dates are compared, so this works without a
+    /// reliable local clock.
+    #[inline]
+    pub fn set_max_token_lifetime(&mut self, lifetime: Duration) -> &mut Self {
+        self.max_token_lifetime = Some(lifetime);
+        self
+    }
+
+    /// Accept `alg` header values that differ from the registered names only
+    /// in case, e.g. `es256` is treated as `ES256`.
+    ///
+    /// `alg` is case sensitive per RFC 7515, so this is off by default and
+    /// such tokens fail verification.
+    #[inline]
+    pub fn set_lenient_alg_case(&mut self, lenient: bool) -> &mut Self {
+        self.lenient_alg_case = lenient;
+        self
+    }
+
+    /// Require that the token `cty` header is `cty`, compared case
+    /// insensitively, otherwise fail with `Error::InvalidContentType`.
+    #[inline]
+    pub fn set_cty(&mut self, cty: impl Into<String>) -> &mut Self {
+        self.cty = Some(cty.into());
+        self
+    }
+
+    #[inline]
+    pub(crate) fn cty(&self) -> Option<&str> {
+        self.cty.as_deref()
+    }
+
+    /// Reject tokens whose header JSON has duplicate member names, e.g. two
+    /// `crit`s, with `Error::InvalidToken`. Otherwise the last value is used,
+    /// and other parsers may disagree on which one applies.
+    ///
+    /// Duplicate `typ`, `cty`, `alg` and `kid` are always rejected.
+    ///
+    /// Off by default.
+    #[inline]
+    pub fn set_strict_header(&mut self, strict: bool) -> &mut Self {
+        self.strict_header = strict;
+        self
+    }
+
+    #[inline]
+    pub(crate) fn strict_header(&self) -> bool {
+        self.strict_header
+    }
+
+    /// Also accept ASN.1 DER encoded ECDSA signatures (as returned by e.g.
+    /// cloud KMS signing APIs) in the signature segment, in addition to the
+    /// fixed length `r || s` form required by RFC 7518.
+    ///
+    /// The form is detected automatically. Off by default.
+    #[inline]
+    pub fn set_accept_der_ecdsa_signatures(&mut self, accept: bool) -> &mut Self {
+        self.accept_der_ecdsa_signatures = accept;
+        self
+    }
+
+    #[inline]
+    #[cfg(feature = "ecdsa")]
+    pub(crate) fn accept_der_ecdsa_signatures(&self) -> bool {
+        self.accept_der_ecdsa_signatures
+    }
+
+    /// Treat `exp`, `nbf` and `iat` values that are too large to be seconds
+    /// (later than the year 5000) as milliseconds since the epoch, a common
+    /// mistake of some issuers.
+    ///
+    /// Off by default. Dates that do not fit in a `SystemTime` always fail with
+    /// `Error::InvalidNumericDate`.
+    #[inline]
+    pub fn set_detect_millisecond_dates(&mut self, detect: bool) -> &mut Self {
+        self.detect_millisecond_dates = detect;
+        self
+    }
+
+    /// Use `now_unix` (seconds since the epoch) as the current time for all
+    /// date checks instead of the system clock, e.g. on targets where the
+    /// time is provided externally.
+    ///
+    /// A time that does not fit in a `SystemTime` fails all tokens with
+    /// `Error::InvalidNumericDate`.
+    #[inline]
+    pub fn set_now_unix(&mut self, now_unix: u64) -> &mut Self {
+        self.now_unix = Some(now_unix);
+        self
+    }
+
+    fn now(&self) -> Option<SystemTime> {
+        match self.now_unix {
+            Some(now) => SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(now)),
+            None => Some(SystemTime::now()),
+        }
+    }
+
+    fn numeric_date(&self, date: u64) -> Result<SystemTime> {
+        // 5138-11-16 in seconds, 1973-03-03 in milliseconds.
+        const MILLISECONDS_THRESHOLD: u64 = 100_000_000_000;
+
+        let secs = if self.detect_millisecond_dates && date >= MILLISECONDS_THRESHOLD {
+            date / 1000
+        } else {
+            date
+        };
+        SystemTime::UNIX_EPOCH
+            .checked_add(Duration::from_secs(secs))
+            .ok_or(Error::InvalidNumericDate)
+    }
+
+    /// Reject tokens whose `alg` is not allowed by `policy` with
+    /// `Error::KeyPolicyViolation`.
+    ///
+    /// JWK set verifiers also check the selected key against `policy`. Keys
+    /// passed directly to `verify_with_options` are not checked, use
+    /// `KeyPolicy::check_key` when loading them.
+    #[inline]
+    pub fn set_key_policy(&mut self, policy: KeyPolicy) -> &mut Self {
+        self.key_policy = Some(policy);
+        self
+    }
+
+    #[inline]
+    pub(crate) fn key_policy(&self) -> Option<&KeyPolicy> {
+        self.key_policy.as_ref()
+    }
+
+    pub(crate) fn without_key_policy(&self) -> Self {
+        Self {
+            key_policy: None,
+            ..self.clone()
+        }
+    }
+
+    /// Reject tokens whose `jti` is revoked according to `revocation` with
+    /// `Error::Revoked`.
+    ///
+    /// Checked after the signature and the other claims. Tokens without a
+    /// `jti` are not affected.
+    pub fn set_revocation(&mut self, revocation: impl Revocation + 'static) -> &mut Self {
+        self.revocation = Some(RevocationRef(Arc::new(revocation)));
+        self
+    }
+
+    /// Add a custom claims validator.
+    ///
+    /// Validators are run in the order they are added, after the signature is
+    /// verified and the other checks passed. The first failure aborts
+    /// verification with its error, e.g. `Error::InvalidClaim`.
+    pub fn add_validator(
+        &mut self,
+        validator: impl Fn(&Claims<Map<String, Value>>) -> Result<()> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.validators.push(ClaimsValidator(Arc::new(validator)));
+        self
+    }
+
+    #[inline]
+    pub(crate) fn has_validators(&self) -> bool {
+        !self.validators.is_empty()
+    }
+
+    pub(crate) fn run_validators(&self, claims: &Claims<Map<String, Value>>) -> Result<()> {
+        for v in &self.validators {
+            (v.0)(claims)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn run_all_validators(
+        &self,
+        claims: &Claims<Map<String, Value>>,
+        errors: &mut Vec<Error>,
+    ) {
+        errors.extend(self.validators.iter().filter_map(|v| (v.0)(claims).err()));
+    }
+
+    pub(crate) fn normalize_alg<'a>(&self, alg: &'a str) -> Cow<'a, str> {
+        if !self.lenient_alg_case {
+            Cow::Borrowed(alg)
+        } else if alg.eq_ignore_ascii_case("EdDSA") {
+            Cow::Borrowed("EdDSA")
+        } else {
+            Cow::Owned(alg.to_ascii_uppercase())
+        }
+    }
+
+    /// Validate claims, returns the matched audiences.
+    pub(crate) fn validate<ExtraClaims>(
+        &self,
+        claims: &Claims<ExtraClaims>,
+    ) -> Result<Vec<String>> {
+        let mut errors = Vec::new();
+        let matched = self.validate_all(claims, &mut errors);
+        match errors.into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(matched),
+        }
+    }
+
+    /// Validate claims without stopping at the first failure. Failures are
+    /// pushed to `errors` in the order they are checked.
+    pub(crate) fn validate_all<ExtraClaims>(
+        &self,
+        claims: &Claims<ExtraClaims>,
+        errors: &mut Vec<Error>,
+    ) -> Vec<String> {
+        let mut date = |d: Option<u64>| match d.map(|d| self.numeric_date(d)).transpose() {
+            Ok(d) => d,
+            Err(e) => {
+                errors.push(e);
+                None
+            }
+        };
+        let exp = date(claims.exp);
+        let nbf = date(claims.nbf);
+        let iat = date(claims.iat);
+
+        if self.check_consistency {
+            if let Some(exp) = exp {
+                if iat.map_or(false, |iat| iat > exp) || nbf.map_or(false, |nbf| nbf > exp) {
+                    errors.push(Error::InconsistentClaims);
+                }
+            }
+        }
+
+        if let Some(max) = self.max_token_lifetime {
+            match (iat, exp) {
+                (Some(iat), Some(exp)) if exp.duration_since(iat).map_or(true, |l| l <= max) => {}
+                _ => errors.push(Error::LifetimeTooLong),
+            }
+        }
+
+        // A date plus leeway that does not fit in a `SystemTime` is in the far
+        // future.
+        match self.now() {
+            Some(now) => {
+                let (exp_leeway, nbf_leeway) = self.leeways(iat, exp);
+                if let Some(exp) = exp {
+                    if exp.checked_add(exp_leeway).map_or(false, |exp| now > exp) {
+                        errors.push(Error::Expired);
+                    }
+                }
+                if let (Some(nbf), Some(max)) = (nbf, self.max_nbf_future) {
+                    if now.checked_add(max).map_or(false, |now| now < nbf) {
+                        errors.push(Error::NbfTooFarInFuture);
+                    }
+                }
+                if let Some(nbf) = nbf {
+                    if now.checked_add(nbf_leeway).map_or(false, |now| now < nbf) {
+                        errors.push(Error::Before);
+                    }
+                }
+                if let Some(iat) = iat {
+                    if now
+                        .checked_add(self.iat_leeway)
+                        .map_or(false, |now| now < iat)
+                    {
+                        errors.push(Error::IssuedInFuture);
+                    }
+                }
+            }
+            None => errors.push(Error::InvalidNumericDate),
+        }
+
+        if !self.issuers.is_empty() {
+            match claims.iss {
+                Some(ref iss) if self.issuers.iter().any(|e| self.iss_matches(e, iss)) => {}
+                _ => errors.push(Error::InvalidIssuer),
+            }
+        }
+
+        let matched: Vec<String> = match &claims.aud {
+            OneOrMany::One(aud) => self.auds.iter().filter(|a| *a == aud).cloned().collect(),
+            OneOrMany::Vec(auds) => self
+                .auds
+                .iter()
+                .filter(|a| auds.contains(a))
+                .cloned()
+                .collect(),
+        };
+        if !self.auds.is_empty() && matched.is_empty() {
+            errors.push(Error::InvalidAudience);
+        }
+
+        if let (Some(revocation), Some(jti)) = (&self.revocation, &claims.jti) {
+            if revocation.0.is_revoked(jti) {
+                errors.push(Error::Revoked);
+            }
+        }
+
+        matched
+    }
+}
+
+/// Result of `verify_with_options`.
+#[derive(Debug)]
+pub struct Verified<ExtraClaims> {
+    header_and_claims: HeaderAndClaims<ExtraClaims>,
+    matched_auds: Vec<String>,
+    key_source: Option<KeySource>,
+}
+
+impl<ExtraClaims> Verified<ExtraClaims> {
+    pub(crate) fn new(
+        header_and_claims: HeaderAndClaims<ExtraClaims>,
+        matched_auds: Vec<String>,
+    ) -> Self {
+        Self {
+            header_and_claims,
+            matched_auds,
+            key_source: None,
+        }
+    }
+
+    #[cfg(feature = "remote-jwks")]
+    pub(crate) fn with_key_source(self, key_source: KeySource) -> Self {
+        Self {
+            key_source: Some(key_source),
+            ..self
+        }
+    }
+
+    #[inline]
+    pub fn header_and_claims(&self) -> &HeaderAndClaims<ExtraClaims> {
+        &self.header_and_claims
+    }
+
+    #[inline]
+    pub fn into_header_and_claims(self) -> HeaderAndClaims<ExtraClaims> {
+        self.header_and_claims
+    }
+
+    /// Audiences configured in `VerifyOptions` that are present in the token,
+    /// in the order they were configured.
+    ///
+    /// Empty if no audiences are configured.
+    #[inline]
+    pub fn matched_auds(&self) -> &[String] {
+        &self.matched_auds
+    }
+
+    /// Where the verification key came from. Only set by
+    /// `RemoteJwksVerifier`.
+    #[inline]
+    pub fn key_source(&self) -> Option<KeySource> {
+        self.key_source
+    }
+}
+
+impl Verified<Map<String, Value>> {
+    /// Deserialize the extra claims, e.g. of a token verified by a
+    /// `JwksProvider`.
+    pub(crate) fn deserialize_extra<ExtraClaims: serde::de::DeserializeOwned>(
+        self,
+    ) -> Result<Verified<ExtraClaims>> {
+        let HeaderAndClaims { header, claims } = self.header_and_claims;
+        Ok(Verified {
+            header_and_claims: HeaderAndClaims {
+                header,
+                claims: Claims {
+                    exp: claims.exp,
+                    nbf: claims.nbf,
+                    iat: claims.iat,
+                    iss: claims.iss,
+                    sub: claims.sub,
+                    aud: claims.aud,
+                    jti: claims.jti,
+                    extra: serde_json::from_value(Value::Object(claims.extra))?,
+                },
+            },
+            matched_auds: self.matched_auds,
+            key_source: self.key_source,
+        })
+    }
+}
+
+/// Result of `inspect`.
+///
+/// Checks that are not configured in `VerifyOptions` are `None`. If the token
+/// is not `well_formed`, no other checks are performed.
+#[non_exhaustive]
+#[derive(Debug, Default)]
+pub struct TokenInspection {
+    pub well_formed: bool,
+    pub signature_valid: bool,
+    /// Whether `alg` is allowed by the key policy.
+    pub alg_allowed: Option<bool>,
+    pub expired: bool,
+    pub not_yet_valid: bool,
+    pub issuer_ok: Option<bool>,
+    pub audience_ok: Option<bool>,
+    /// All problems found, see `verify_verbose`.
+    pub errors: Vec<Error>,
+}
+
+impl TokenInspection {
+    pub(crate) fn malformed(e: Error) -> Self {
+        Self {
+            errors: vec![e],
+            ..Self::default()
+        }
+    }
+
+    /// No problems were found.
+    #[inline]
+    pub fn is_valid(&self) -> bool {
+        self.well_formed && self.errors.is_empty()
+    }
+}